@@ -0,0 +1,34 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// flowerchat-protocol
+// Copyright (C) 2025  Nikita Podvirnyi <krypt0nn@vk.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use flowerchat_protocol::share_link::ShareLink;
+
+fuzz_target!(|bytes: &[u8]| {
+    // Parsing must never panic, and parsed links must serialize back to
+    // equal links.
+    if let Ok(link) = ShareLink::from_bytes(bytes) {
+        let serialized = link.to_bytes()
+            .expect("failed to serialize parsed share link");
+
+        assert_eq!(ShareLink::from_bytes(serialized).ok(), Some(link));
+    }
+});
@@ -16,8 +16,19 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::io::Read;
+
 use libflowerpot::crypto::*;
 
+/// Maximal size of the decompressed space sharing link.
+pub const MAX_LINK_SIZE: usize = 1 << 20;
+
+/// Maximal amount of shards in the space sharing link.
+pub const MAX_SHARDS: usize = 1024;
+
+/// Prefix of the space sharing link URI.
+pub const URI_PREFIX: &str = "flowerchat://space/";
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("zstd error: {0}")]
@@ -26,21 +37,141 @@ pub enum Error {
     #[error("invalid base64 format")]
     Base64,
 
+    #[error("invalid space sharing link uri")]
+    InvalidUri,
+
     #[error("invalid space sharing link format: {0}")]
     InvalidFormat(u8),
 
     #[error("invalid public key format")]
-    InvalidPublicKey
+    InvalidPublicKey,
+
+    #[error("space sharing link is truncated")]
+    Truncated,
+
+    #[error("space sharing link contains trailing data")]
+    TrailingData,
+
+    #[error("space sharing link is larger than {MAX_LINK_SIZE} bytes")]
+    TooLarge,
+
+    #[error("space sharing link contains too many shards: {0}")]
+    TooManyShards(usize),
+
+    #[error("space sharing link contains too long string: {0} bytes")]
+    TooLong(usize),
+
+    #[error("space sharing link contains invalid utf-8 string")]
+    InvalidString,
+
+    #[error("space sharing link must be signed to contain title, description or creation time")]
+    Unsigned,
+
+    #[error("space sharing link can only be signed by the space creator")]
+    InvalidSigner,
+
+    #[error("failed to sign space sharing link")]
+    Sign,
+
+    #[error("invalid space sharing link signature")]
+    InvalidSignature
 }
 
+/// Context string of the signed link payload hash.
+const SIGNATURE_CONTEXT: &str = "flowerchat share link v1";
+
 /// Standard format of sharing space with other people. This link contains
 /// hash of the root block of the space's blockchain, public key of its creator
 /// and list of shards for pool bootstrapping.
+///
+/// Since format version 1 the link can also contain title and description of
+/// the space and time of the link creation. Such links are signed by the space
+/// creator so they can't be modified by anybody else.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ShareLink {
     root_block: Hash,
     public_key: PublicKey,
-    shards: Box<[String]>
+    shards: Box<[String]>,
+    title: Option<String>,
+    description: Option<String>,
+    created_at: Option<u64>,
+    signature: Option<Signature>
+}
+
+/// Read fields of the serialized link checking its length.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.0.len() < len {
+            return Err(Error::Truncated);
+        }
+
+        let (value, rest) = self.0.split_at(len);
+
+        self.0 = rest;
+
+        Ok(value)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut value = [0; N];
+
+        value.copy_from_slice(self.take(N)?);
+
+        Ok(value)
+    }
+
+    fn take_string(&mut self) -> Result<String, Error> {
+        let len = u16::from_le_bytes(self.take_array()?) as usize;
+
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| Error::InvalidString)
+    }
+}
+
+fn push_string(bytes: &mut Vec<u8>, value: &str) -> Result<(), Error> {
+    if value.len() > u16::MAX as usize {
+        return Err(Error::TooLong(value.len()));
+    }
+
+    bytes.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(value.as_bytes());
+
+    Ok(())
+}
+
+/// Decompress the link limiting its size to prevent decompression bombs.
+fn decompress(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let decoder = zstd::stream::read::Decoder::new(bytes)
+        .map_err(Error::Zstd)?;
+
+    let mut link = Vec::new();
+
+    decoder.take(MAX_LINK_SIZE as u64 + 1)
+        .read_to_end(&mut link)
+        .map_err(Error::Zstd)?;
+
+    if link.len() > MAX_LINK_SIZE {
+        return Err(Error::TooLarge);
+    }
+
+    Ok(link)
+}
+
+fn compress(format: u8, link: &[u8]) -> Result<Box<[u8]>, Error> {
+    if link.len() > MAX_LINK_SIZE {
+        return Err(Error::TooLarge);
+    }
+
+    let mut compressed_link = vec![format];
+
+    let link = zstd::encode_all(link, 20)
+        .map_err(Error::Zstd)?;
+
+    compressed_link.extend(link);
+
+    Ok(compressed_link.into_boxed_slice())
 }
 
 impl ShareLink {
@@ -55,8 +186,56 @@ impl ShareLink {
             public_key: public_key.into(),
             shards: shards.into_iter()
                 .map(|address| address.to_string())
-                .collect()
+                .collect(),
+            title: None,
+            description: None,
+            created_at: None,
+            signature: None
+        }
+    }
+
+    /// Set title of the space. Link must be signed after this.
+    pub fn with_title(mut self, title: impl ToString) -> Self {
+        self.title = Some(title.to_string())
+            .filter(|title| !title.is_empty());
+
+        self.signature = None;
+
+        self
+    }
+
+    /// Set description of the space. Link must be signed after this.
+    pub fn with_description(mut self, description: impl ToString) -> Self {
+        self.description = Some(description.to_string())
+            .filter(|description| !description.is_empty());
+
+        self.signature = None;
+
+        self
+    }
+
+    /// Set link creation time as UNIX timestamp. Link must be signed after
+    /// this.
+    pub fn with_created_at(mut self, created_at: u64) -> Self {
+        self.created_at = Some(created_at)
+            .filter(|created_at| *created_at != 0);
+
+        self.signature = None;
+
+        self
+    }
+
+    /// Sign the link with the secret key of the space creator.
+    pub fn sign(mut self, secret_key: &SecretKey) -> Result<Self, Error> {
+        if secret_key.public_key() != self.public_key {
+            return Err(Error::InvalidSigner);
         }
+
+        let hash = Self::payload_hash(&self.payload()?);
+
+        self.signature = Some(Signature::create(secret_key, hash).map_err(|_| Error::Sign)?);
+
+        Ok(self)
     }
 
     /// Get root block's hash for the current space.
@@ -74,74 +253,182 @@ impl ShareLink {
         &self.shards
     }
 
+    /// Get title of the space if it's provided.
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// Get description of the space if it's provided.
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Get link creation time as UNIX timestamp if it's provided.
+    pub const fn created_at(&self) -> Option<u64> {
+        self.created_at
+    }
+
+    /// Check if the link is signed by the space creator.
+    pub const fn is_signed(&self) -> bool {
+        self.signature.is_some()
+    }
+
+    /// Serialize fields of the format version 1 link except its signature.
+    fn payload(&self) -> Result<Vec<u8>, Error> {
+        if self.shards.len() > MAX_SHARDS {
+            return Err(Error::TooManyShards(self.shards.len()));
+        }
+
+        let mut payload = vec![1];
+
+        payload.extend_from_slice(&self.root_block.0);
+        payload.extend_from_slice(&self.public_key.to_bytes());
+        payload.extend_from_slice(&self.created_at.unwrap_or_default().to_le_bytes());
+
+        push_string(&mut payload, self.title.as_deref().unwrap_or_default())?;
+        push_string(&mut payload, self.description.as_deref().unwrap_or_default())?;
+
+        payload.extend_from_slice(&(self.shards.len() as u16).to_le_bytes());
+
+        for address in &self.shards {
+            push_string(&mut payload, address)?;
+        }
+
+        Ok(payload)
+    }
+
+    #[inline]
+    fn payload_hash(payload: &[u8]) -> Hash {
+        Hash::from(blake3::derive_key(SIGNATURE_CONTEXT, payload))
+    }
+
     /// Serialize current space sharing link to bytes.
+    ///
+    /// Signed links are serialized using format version 1, and not signed ones
+    /// using format version 0.
     pub fn to_bytes(&self) -> Result<Box<[u8]>, Error> {
+        if let Some(signature) = &self.signature {
+            let mut link = self.payload()?;
+
+            link.extend_from_slice(&signature.to_bytes());
+
+            return compress(1, &link[1..]);
+        }
+
+        if self.title.is_some() || self.description.is_some() || self.created_at.is_some() {
+            return Err(Error::Unsigned);
+        }
+
+        if self.shards.len() > MAX_SHARDS {
+            return Err(Error::TooManyShards(self.shards.len()));
+        }
+
         let mut link = Vec::new();
 
         link.extend_from_slice(&self.root_block.0);
         link.extend_from_slice(&self.public_key.to_bytes());
 
         for address in &self.shards {
-            let len = address.len();
-
-            if len <= u16::MAX as usize {
-                link.extend_from_slice(&(len as u16).to_le_bytes());
-                link.extend_from_slice(address.as_bytes());
-            }
+            push_string(&mut link, address)?;
         }
 
-        let mut compressed_link = vec![0];
+        compress(0, &link)
+    }
 
-        let link = zstd::encode_all(&mut link.as_slice(), 20)
-            .map_err(Error::Zstd)?;
+    /// Deserialize signed space sharing link of format version 1 verifying
+    /// its signature.
+    fn from_bytes_v1(bytes: &[u8]) -> Result<Self, Error> {
+        let mut payload = vec![1];
 
-        compressed_link.extend(link);
+        payload.extend(decompress(bytes)?);
 
-        Ok(compressed_link.into_boxed_slice())
-    }
+        let mut reader = Reader(&payload[1..]);
 
-    /// Deserialize space sharing link from bytes.
-    pub fn from_bytes(bytes: impl AsRef<[u8]>) -> Result<Self, Error> {
-        let bytes = bytes.as_ref();
+        let root_block = Hash::from(reader.take_array::<32>()?);
+
+        let public_key = PublicKey::from_bytes(reader.take_array::<33>()?)
+            .ok_or(Error::InvalidPublicKey)?;
+
+        let created_at = u64::from_le_bytes(reader.take_array()?);
+
+        let title = reader.take_string()?;
+        let description = reader.take_string()?;
+
+        let shards_num = u16::from_le_bytes(reader.take_array()?) as usize;
 
-        if bytes[0] != 0 {
-            return Err(Error::InvalidFormat(bytes[0]));
+        if shards_num > MAX_SHARDS {
+            return Err(Error::TooManyShards(shards_num));
         }
 
-        let bytes = zstd::decode_all(&mut &bytes[1..]).map_err(Error::Zstd)?;
+        let shards = (0..shards_num)
+            .map(|_| reader.take_string())
+            .collect::<Result<Box<[String]>, Error>>()?;
 
-        let mut root_block = [0; 32];
-        let mut public_key = [0; 33];
+        let signature = Signature::from_bytes(&reader.take_array::<65>()?)
+            .ok_or(Error::InvalidSignature)?;
 
-        root_block.copy_from_slice(&bytes[0..32]);
-        public_key.copy_from_slice(&bytes[32..65]);
+        if !reader.0.is_empty() {
+            return Err(Error::TrailingData);
+        }
 
-        let len = bytes.len();
-        let mut i = 65;
+        // Verify signature over all the fields preceding it.
+        let hash = Self::payload_hash(&payload[..payload.len() - 65]);
 
-        let mut shards = Vec::new();
+        match signature.verify(hash) {
+            Ok((true, signer)) if signer == public_key => (),
+            _ => return Err(Error::InvalidSignature)
+        }
 
-        while i < len {
-            let mut address_len = [0; 2];
+        Ok(Self {
+            root_block,
+            public_key,
+            shards,
+            title: Some(title).filter(|title| !title.is_empty()),
+            description: Some(description).filter(|description| !description.is_empty()),
+            created_at: Some(created_at).filter(|created_at| *created_at != 0),
+            signature: Some(signature)
+        })
+    }
 
-            address_len.copy_from_slice(&bytes[i..i + 2]);
+    /// Deserialize space sharing link from bytes.
+    pub fn from_bytes(bytes: impl AsRef<[u8]>) -> Result<Self, Error> {
+        let (format, bytes) = bytes.as_ref()
+            .split_first()
+            .ok_or(Error::Truncated)?;
+
+        match format {
+            0 => (),
+            1 => return Self::from_bytes_v1(bytes),
+            _ => return Err(Error::InvalidFormat(*format))
+        }
+
+        let bytes = decompress(bytes)?;
+
+        let mut reader = Reader(&bytes);
 
-            let address_len = u16::from_le_bytes(address_len) as usize;
+        let root_block = Hash::from(reader.take_array::<32>()?);
 
-            let mut address = vec![0; address_len];
+        let public_key = PublicKey::from_bytes(reader.take_array::<33>()?)
+            .ok_or(Error::InvalidPublicKey)?;
 
-            address.copy_from_slice(&bytes[i + 2..i + 2 + address_len]);
+        let mut shards = Vec::new();
 
-            shards.push(String::from_utf8_lossy(&address).to_string());
+        while !reader.0.is_empty() {
+            if shards.len() == MAX_SHARDS {
+                return Err(Error::TooManyShards(shards.len() + 1));
+            }
 
-            i += address_len + 2;
+            shards.push(reader.take_string()?);
         }
 
         Ok(Self {
-            root_block: Hash::from(root_block),
-            public_key: PublicKey::from_bytes(public_key)
-                .ok_or(Error::InvalidPublicKey)?,
-            shards: shards.into_boxed_slice()
+            root_block,
+            public_key,
+            shards: shards.into_boxed_slice(),
+            title: None,
+            description: None,
+            created_at: None,
+            signature: None
         })
     }
 
@@ -156,6 +443,27 @@ impl ShareLink {
 
         Self::from_bytes(link)
     }
+
+    /// Serialize current link to `flowerchat://space/<link>` URI.
+    pub fn to_uri(&self) -> Result<String, Error> {
+        Ok(format!("{URI_PREFIX}{}", self.to_base64()?))
+    }
+
+    /// Parse space sharing link either from its URI or base64 string.
+    pub fn parse(link: impl AsRef<str>) -> Result<Self, Error> {
+        let link = link.as_ref().trim();
+
+        let is_uri = link.get(..URI_PREFIX.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(URI_PREFIX));
+
+        if is_uri {
+            Self::from_base64(link[URI_PREFIX.len()..].trim_end_matches('/'))
+        } else if link.contains("://") {
+            Err(Error::InvalidUri)
+        } else {
+            Self::from_base64(link)
+        }
+    }
 }
 
 #[test]
@@ -184,3 +492,221 @@ fn test_serialize() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn test_signed() -> Result<(), Error> {
+    use rand_chacha::ChaCha20Rng;
+    use rand_chacha::rand_core::SeedableRng;
+
+    let mut rng = ChaCha20Rng::seed_from_u64(123);
+
+    let secret_key = SecretKey::random(&mut rng);
+
+    let link = ShareLink::new(
+        Hash::default(),
+        secret_key.public_key(),
+        [String::from("test 1"), String::from("test 2")]
+    );
+
+    // Metadata can't be serialized without signature.
+    let unsigned = link.clone().with_title("test");
+
+    assert!(matches!(unsigned.to_bytes(), Err(Error::Unsigned)));
+
+    // Only space creator can sign the link.
+    let another_key = SecretKey::random(&mut rng);
+
+    assert!(matches!(unsigned.clone().sign(&another_key), Err(Error::InvalidSigner)));
+
+    let signed = unsigned.with_description("test description")
+        .with_created_at(1234567890)
+        .sign(&secret_key)?;
+
+    assert_eq!(signed.to_bytes()?[0], 1);
+    assert_eq!(signed, ShareLink::from_base64(signed.to_base64()?)?);
+
+    assert_eq!(signed.title(), Some("test"));
+    assert_eq!(signed.description(), Some("test description"));
+    assert_eq!(signed.created_at(), Some(1234567890));
+
+    // Shards can't be injected into a signed link.
+    let mut tampered = signed.clone();
+
+    tampered.shards = Box::new([String::from("malicious")]);
+
+    assert!(matches!(ShareLink::from_bytes(tampered.to_bytes()?), Err(Error::InvalidSignature)));
+
+    // Not signed links still use format version 0.
+    assert_eq!(link.to_bytes()?[0], 0);
+    assert_eq!(link, ShareLink::from_bytes(link.to_bytes()?)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_malformed() -> Result<(), Error> {
+    use rand_chacha::ChaCha20Rng;
+    use rand_chacha::rand_core::SeedableRng;
+
+    let mut rng = ChaCha20Rng::seed_from_u64(123);
+
+    let secret_key = SecretKey::random(&mut rng);
+
+    let mut link = Vec::new();
+
+    link.extend_from_slice(&Hash::default().0);
+    link.extend_from_slice(&secret_key.public_key().to_bytes());
+
+    let compress_v0 = |link: &[u8]| {
+        let mut compressed_link = vec![0];
+
+        compressed_link.extend(zstd::encode_all(link, 0).unwrap());
+
+        compressed_link
+    };
+
+    assert!(matches!(ShareLink::from_bytes([]), Err(Error::Truncated)));
+    assert!(matches!(ShareLink::from_bytes([2]), Err(Error::InvalidFormat(2))));
+    assert!(matches!(ShareLink::from_bytes(compress_v0(&link[..40])), Err(Error::Truncated)));
+
+    // Address length exceeds remaining bytes.
+    let mut truncated = link.clone();
+
+    truncated.extend_from_slice(&[10, 0, b'a']);
+
+    assert!(matches!(ShareLink::from_bytes(compress_v0(&truncated)), Err(Error::Truncated)));
+
+    // Address is not a valid utf-8 string.
+    let mut invalid = link.clone();
+
+    invalid.extend_from_slice(&[2, 0, 0xC3, 0x28]);
+
+    assert!(matches!(ShareLink::from_bytes(compress_v0(&invalid)), Err(Error::InvalidString)));
+
+    // Too many shards.
+    let mut shards = link.clone();
+
+    for _ in 0..=MAX_SHARDS {
+        shards.extend_from_slice(&[1, 0, b'a']);
+    }
+
+    assert!(matches!(ShareLink::from_bytes(compress_v0(&shards)), Err(Error::TooManyShards(_))));
+
+    // Decompression bomb.
+    let mut bomb = link.clone();
+
+    bomb.resize(MAX_LINK_SIZE * 2, 0);
+
+    assert!(matches!(ShareLink::from_bytes(compress_v0(&bomb)), Err(Error::TooLarge)));
+
+    // Trailing data after signature.
+    let signed = ShareLink::new(Hash::default(), secret_key.public_key(), ["test"])
+        .with_title("test")
+        .sign(&secret_key)?;
+
+    let mut payload = decompress(&signed.to_bytes()?[1..])?;
+
+    payload.push(0);
+
+    let mut trailing = vec![1];
+
+    trailing.extend(zstd::encode_all(payload.as_slice(), 0).unwrap());
+
+    assert!(matches!(ShareLink::from_bytes(trailing), Err(Error::TrailingData)));
+
+    // Every truncated valid link must be rejected.
+    let bytes = signed.to_bytes()?;
+
+    for len in 0..bytes.len() {
+        assert!(ShareLink::from_bytes(&bytes[..len]).is_err());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_uri() -> Result<(), Error> {
+    use rand_chacha::ChaCha20Rng;
+    use rand_chacha::rand_core::SeedableRng;
+
+    let mut rng = ChaCha20Rng::seed_from_u64(123);
+
+    let secret_key = SecretKey::random(&mut rng);
+
+    let link = ShareLink::new(Hash::default(), secret_key.public_key(), ["test"]);
+
+    let uri = link.to_uri()?;
+
+    assert!(uri.starts_with("flowerchat://space/"));
+
+    assert_eq!(link, ShareLink::parse(&uri)?);
+    assert_eq!(link, ShareLink::parse(format!("  {uri}/\n"))?);
+    assert_eq!(link, ShareLink::parse(uri.replace("flowerchat", "FlowerChat"))?);
+    assert_eq!(link, ShareLink::parse(link.to_base64()?)?);
+
+    assert!(matches!(ShareLink::parse(uri.replace("space", "room")), Err(Error::InvalidUri)));
+
+    Ok(())
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #![proptest_config(proptest::prelude::ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn test_roundtrip(
+        seed: u64,
+        root_block: [u8; 32],
+        shards in proptest::collection::vec(".{0,64}", 0..16),
+        title in proptest::option::of(".{0,64}"),
+        description in proptest::option::of(".{0,256}"),
+        created_at in proptest::option::of(proptest::num::u64::ANY),
+        sign: bool
+    ) {
+        use rand_chacha::ChaCha20Rng;
+        use rand_chacha::rand_core::SeedableRng;
+
+        let secret_key = SecretKey::random(&mut ChaCha20Rng::seed_from_u64(seed));
+
+        let mut link = ShareLink::new(root_block, secret_key.public_key(), shards);
+
+        if let Some(title) = title {
+            link = link.with_title(title);
+        }
+
+        if let Some(description) = description {
+            link = link.with_description(description);
+        }
+
+        if let Some(created_at) = created_at {
+            link = link.with_created_at(created_at);
+        }
+
+        let has_metadata = link.title().is_some()
+            || link.description().is_some()
+            || link.created_at().is_some();
+
+        if sign || has_metadata {
+            link = link.sign(&secret_key).unwrap();
+        }
+
+        proptest::prop_assert_eq!(&link, &ShareLink::from_base64(link.to_base64().unwrap()).unwrap());
+    }
+
+    #[test]
+    fn test_arbitrary_bytes(bytes in proptest::collection::vec(proptest::num::u8::ANY, 0..512)) {
+        let _ = ShareLink::from_bytes(bytes);
+    }
+
+    #[test]
+    fn test_arbitrary_payload(
+        format in 0u8..2,
+        payload in proptest::collection::vec(proptest::num::u8::ANY, 0..512)
+    ) {
+        let mut bytes = vec![format];
+
+        bytes.extend(zstd::encode_all(payload.as_slice(), 0).unwrap());
+
+        let _ = ShareLink::from_bytes(bytes);
+    }
+}
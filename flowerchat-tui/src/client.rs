@@ -31,6 +31,7 @@ use crate::database::public_room::{PublicRoomRecord, PublicRoomInfo};
 use crate::database::public_message::{
     PublicRoomMessageRecord, PublicRoomMessageInfo
 };
+use crate::database::outbox::{OutboxRecord, OutboxStatus};
 use crate::database::Database;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -45,48 +46,93 @@ pub struct HandlerEvent {
     pub event: Events
 }
 
+/// Read the next block using the provided blockchain viewer and decode its
+/// transactions into flowerchat events. Return `None` if there's no block to
+/// read.
+pub async fn read_block(
+    viewer: &mut Viewer
+) -> anyhow::Result<Option<Vec<HandlerEvent>>> {
+    let Some(block) = viewer.forward().await else {
+        return Ok(None);
+    };
+
+    let BlockContent::Transactions(transactions) = block.block.content() else {
+        return Ok(Some(Vec::new()));
+    };
+
+    let mut events = Vec::with_capacity(transactions.len());
+
+    for transaction in transactions {
+        let (
+            is_valid,
+            transaction_hash,
+            transaction_public_key
+        ) = transaction.verify().context("failed to verify transaction")?;
+
+        if is_valid {
+            events.push(HandlerEvent {
+                block_hash: block.hash,
+                block_public_key: block.public_key.clone(),
+                block_timestamp: *block.block.timestamp(),
+
+                transaction_hash,
+                transaction_public_key,
+
+                event: Events::deserialize(&mut transaction.data())
+                    .context("failed to deserialize transaction into flowerchat event")?
+            });
+        }
+    }
+
+    Ok(Some(events))
+}
+
 /// Read blocks using the provided blockchain viewer, decode transactions into
-/// flowerchat events and send them to the `handler` callback.
+/// flowerchat events and send events of every block to the `handler` callback.
 ///
 /// If `handler` returns `Err(E)` then this function will be terminated and
 /// `Ok(Some(E))` will be returned.
-pub async fn read_events<E>(
+pub async fn read_blocks<E>(
     mut viewer: Viewer,
-    mut handler: impl FnMut(HandlerEvent) -> Result<(), E>
+    mut handler: impl FnMut(Vec<HandlerEvent>) -> Result<(), E>
 ) -> anyhow::Result<Option<E>> {
     loop {
-        if let Some(block) = viewer.forward().await &&
-            let BlockContent::Transactions(transactions) = block.block.content()
+        if let Some(events) = read_block(&mut viewer).await? &&
+            let Err(err) = handler(events)
         {
-            for transaction in transactions {
-                let (
-                    is_valid,
-                    transaction_hash,
-                    transaction_public_key
-                ) = transaction.verify().context("failed to verify transaction")?;
-
-                if is_valid {
-                    let result = handler(HandlerEvent {
-                        block_hash: block.hash,
-                        block_public_key: block.public_key.clone(),
-                        block_timestamp: *block.block.timestamp(),
-
-                        transaction_hash,
-                        transaction_public_key,
-
-                        event: Events::deserialize(&mut transaction.data())
-                            .context("failed to deserialize transaction into flowerchat event")?
-                    });
-
-                    if let Err(err) = result {
-                        return Ok(Some(err));
-                    }
-                }
-            }
+            return Ok(Some(err));
         }
     }
 }
 
+/// Read all the currently known blocks using the provided blockchain viewer
+/// and decode their transactions into flowerchat events.
+pub async fn read_chain(
+    mut viewer: Viewer
+) -> anyhow::Result<Vec<HandlerEvent>> {
+    let mut events = Vec::new();
+
+    while let Some(block) = read_block(&mut viewer).await? {
+        events.extend(block);
+    }
+
+    Ok(events)
+}
+
+/// Read blocks using the provided blockchain viewer, decode transactions into
+/// flowerchat events and send them to the `handler` callback.
+///
+/// If `handler` returns `Err(E)` then this function will be terminated and
+/// `Ok(Some(E))` will be returned.
+pub async fn read_events<E>(
+    viewer: Viewer,
+    mut handler: impl FnMut(HandlerEvent) -> Result<(), E>
+) -> anyhow::Result<Option<E>> {
+    read_blocks(viewer, move |events| {
+        events.into_iter().try_for_each(&mut handler)
+    }).await
+}
+
 pub enum Update {
     /// Iterating over the network blocks to verify the blockchain integrity
     /// until we find not yet processed blocks.
@@ -118,19 +164,192 @@ pub enum Update {
         transaction_hash: Hash,
 
         /// Timestamp of when the block was made.
-        block_timestamp: UtcDateTime
+        block_timestamp: UtcDateTime,
+
+        /// Processed event.
+        event: Events
+    }
+}
+
+fn find_or_create_user(
+    database: &Database,
+    space_id: i64,
+    public_key: PublicKey
+) -> anyhow::Result<UserRecord> {
+    let user = UserRecord::find(
+        database.clone(),
+        space_id,
+        &public_key
+    ).context("failed to find user")?;
+
+    match user {
+        Some(user) => Ok(user),
+        None => UserRecord::create(database.clone(), &UserInfo {
+            space_id,
+            public_key,
+            nickname: None
+        }).context("failed to create user")
+    }
+}
+
+/// Store not yet handled event in the database and mark it as handled. Return
+/// `false` if the event was skipped.
+fn handle_event(
+    database: &Database,
+    space: &SpaceRecord,
+    event: &HandlerEvent
+) -> anyhow::Result<bool> {
+    // Stop announcing our own transaction once it's in the blockchain,
+    // even if its event is skipped.
+    let outgoing = OutboxRecord::find(
+        database.clone(),
+        space.id(),
+        &event.transaction_hash
+    ).context("failed to find outgoing transaction")?;
+
+    if let Some(mut outgoing) = outgoing {
+        outgoing.update_status(OutboxStatus::Included(event.block_hash))
+            .context("failed to mark outgoing transaction as included")?;
+    }
+
+    match &event.event {
+        Events::CreatePublicRoom(info) => {
+            let author = find_or_create_user(
+                database,
+                space.id(),
+                event.transaction_public_key.clone()
+            )?;
+
+            PublicRoomRecord::create(database.clone(), &PublicRoomInfo {
+                space_id: space.id(),
+                name: info.name().to_string(),
+                author_id: author.id(),
+                block_hash: event.block_hash,
+                transaction_hash: event.transaction_hash
+            }).context("failed to create public room")?;
+        }
+
+        Events::PublicRoomMessage(info) => {
+            let user = find_or_create_user(
+                database,
+                space.id(),
+                event.transaction_public_key.clone()
+            )?;
+
+            let room = PublicRoomRecord::find(
+                database.clone(),
+                space.id(),
+                info.room_name()
+            ).context("failed to find public room")?;
+
+            // Skip event handling if room doesn't exist.
+            let Some(room) = room else {
+                return Ok(false);
+            };
+
+            PublicRoomMessageRecord::create(database.clone(), &PublicRoomMessageInfo {
+                room_id: room.id(),
+                user_id: user.id(),
+                block_hash: event.block_hash,
+                transaction_hash: event.transaction_hash,
+                timestamp: event.block_timestamp,
+                content: info.content().to_string()
+            }).context("failed to create public room message")?;
+        }
     }
+
+    database.mark_handled(
+        space.id(),
+        event.block_hash,
+        event.transaction_hash
+    ).context("failed to mark transaction as handled")?;
+
+    Ok(true)
+}
+
+/// Store events of a single block within one database transaction so the sync
+/// can't be interrupted in the middle of it. Return updates which should be
+/// reported after the transaction is committed.
+fn handle_block(
+    database: &Database,
+    space: &SpaceRecord,
+    events: Vec<HandlerEvent>,
+    verification_done: &mut bool,
+    curr_timestamp: f32
+) -> anyhow::Result<Vec<Update>> {
+    let mut updates = Vec::with_capacity(events.len());
+
+    database.transaction(|| {
+        for event in events {
+            let is_handled = database.is_handled(
+                space.id(),
+                event.block_hash,
+                event.transaction_hash
+            ).context("failed to verify if transaction is handled")?;
+
+            if is_handled {
+                if !*verification_done {
+                    updates.push(Update::Verification {
+                        block_hash: event.block_hash,
+                        transaction_hash: event.transaction_hash,
+                        block_timestamp: event.block_timestamp,
+                        estimated_progress: event.block_timestamp.unix_timestamp() as f32 / curr_timestamp
+                    });
+                }
+
+                continue;
+            }
+
+            if !*verification_done {
+                updates.push(Update::VerificationDone);
+
+                *verification_done = true;
+            }
+
+            if handle_event(database, space, &event)? {
+                updates.push(Update::NewEvent {
+                    block_hash: event.block_hash,
+                    transaction_hash: event.transaction_hash,
+                    block_timestamp: event.block_timestamp,
+                    event: event.event
+                });
+            }
+        }
+
+        space.update_synced_at(UtcDateTime::now())
+            .context("failed to update space synchronization time")?;
+
+        Ok(())
+    })?;
+
+    Ok(updates)
+}
+
+/// Store not yet handled events in the database within a single transaction.
+pub fn index_events(
+    database: &Database,
+    space: &SpaceRecord,
+    events: Vec<HandlerEvent>
+) -> anyhow::Result<()> {
+    handle_block(database, space, events, &mut true, 0.0)?;
+
+    Ok(())
 }
 
 /// Read blocks using the provided blockchain viewer, decode transactions into
-/// flowerchat events and process them using the database entry.
+/// flowerchat events and process them using the database entry. Events of
+/// every block are stored within a single database transaction.
 pub async fn run(
     database: Database,
-    viewer: Viewer,
+    mut viewer: Viewer,
     mut updater: impl FnMut(Update)
 ) -> anyhow::Result<()> {
-    let space = SpaceRecord::find(database.clone(), viewer.root_block())
-        .context("failed to find space in the database with the viewer's root block")?;
+    let root_block = *viewer.root_block();
+
+    let space = database.spawn(move |database| {
+        SpaceRecord::find(database, &root_block)
+            .context("failed to find space in the database with the viewer's root block")
+    }).await?;
 
     let Some(space) = space else {
         anyhow::bail!("space with requested hash is not stored in the database");
@@ -146,115 +365,36 @@ pub async fn run(
         verification_done = true;
     }
 
-    let result = read_events(viewer, move |event| -> anyhow::Result<()> {
-        let is_handled = database.is_handled(
-            space.id(),
-            event.block_hash,
-            event.transaction_hash
-        ).context("failed to verify if transaction is handled")?;
+    loop {
+        let Some(events) = read_block(&mut viewer).await? else {
+            continue;
+        };
 
-        if !is_handled {
-            if !verification_done {
-                updater(Update::VerificationDone);
+        if events.is_empty() {
+            continue;
+        }
 
-                verification_done = true;
-            }
+        let space = space.clone();
+        let mut block_verification_done = verification_done;
 
-            fn find_or_create_user(
-                database: Database,
-                space_id: i64,
-                public_key: PublicKey
-            ) -> anyhow::Result<UserRecord> {
-                let user = UserRecord::find(
-                    database.clone(),
-                    space_id,
-                    &public_key
-                ).context("failed to find user")?;
-
-                match user {
-                    Some(user) => Ok(user),
-                    None => UserRecord::create(database, &UserInfo {
-                        space_id,
-                        public_key,
-                        nickname: None
-                    }).context("failed to create user")
-                }
-            }
+        // Database queries are blocking so they're executed outside of the
+        // async runtime.
+        let (updates, block_verification_done) = database.spawn(move |database| {
+            let updates = handle_block(
+                &database,
+                &space,
+                events,
+                &mut block_verification_done,
+                curr_timestamp
+            )?;
 
-            match event.event {
-                Events::CreatePublicRoom(info) => {
-                    let author = find_or_create_user(
-                        database.clone(),
-                        space.id(),
-                        event.transaction_public_key
-                    )?;
-
-                    PublicRoomRecord::create(database.clone(), &PublicRoomInfo {
-                        space_id: space.id(),
-                        name: info.name().to_string(),
-                        author_id: author.id(),
-                        block_hash: event.block_hash,
-                        transaction_hash: event.transaction_hash
-                    }).context("failed to create public room")?;
-                }
-
-                Events::PublicRoomMessage(info) => {
-                    let user = find_or_create_user(
-                        database.clone(),
-                        space.id(),
-                        event.transaction_public_key
-                    )?;
-
-                    let room = PublicRoomRecord::find(
-                        database.clone(),
-                        space.id(),
-                        info.room_name()
-                    ).context("failed to find public room")?;
-
-                    // Skip event handling if room doesn't exist.
-                    let Some(room) = room else {
-                        return Ok(());
-                    };
-
-                    PublicRoomMessageRecord::create(database.clone(), &PublicRoomMessageInfo {
-                        room_id: room.id(),
-                        user_id: user.id(),
-                        block_hash: event.block_hash,
-                        transaction_hash: event.transaction_hash,
-                        timestamp: event.block_timestamp,
-                        content: info.content().to_string()
-                    }).context("failed to create public room message")?;
-                }
-            }
-
-            database.mark_handled(
-                space.id(),
-                event.block_hash,
-                event.transaction_hash
-            ).context("failed to mark transaction as handled")?;
+            Ok((updates, block_verification_done))
+        }).await?;
 
-            updater(Update::NewEvent {
-                block_hash: event.block_hash,
-                transaction_hash: event.transaction_hash,
-                block_timestamp: event.block_timestamp
-            });
-        }
+        verification_done = block_verification_done;
 
-        else if !verification_done {
-            updater(Update::Verification {
-                block_hash: event.block_hash,
-                transaction_hash: event.transaction_hash,
-                block_timestamp: event.block_timestamp,
-                estimated_progress: event.block_timestamp.unix_timestamp() as f32 / curr_timestamp
-            });
+        for update in updates {
+            updater(update);
         }
-
-        Ok(())
-    }).await?;
-
-    if let Some(err) = result {
-        anyhow::bail!(err);
     }
-
-    Ok(())
 }
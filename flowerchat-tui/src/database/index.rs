@@ -0,0 +1,289 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// flowerchat
+// Copyright (C) 2025  Nikita Podvirnyi <krypt0nn@vk.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+
+use libflowerpot::crypto::*;
+
+use super::space::SpaceRecord;
+
+fn read_hash(row: &rusqlite::Row, column: &str) -> rusqlite::Result<String> {
+    Ok(Hash::from(row.get::<_, [u8; 32]>(column)?).to_base64())
+}
+
+fn read_public_key(row: &rusqlite::Row, column: &str) -> rusqlite::Result<String> {
+    PublicKey::from_bytes(row.get::<_, [u8; 33]>(column)?)
+        .map(|public_key| public_key.to_base64())
+        .ok_or(rusqlite::Error::InvalidQuery)
+}
+
+/// Difference between two indexes of the same space.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Discrepancy {
+    /// Entry is present only in the expected index.
+    Missing {
+        key: String,
+        value: String
+    },
+
+    /// Entry is present only in the local index.
+    Unexpected {
+        key: String,
+        value: String
+    },
+
+    /// Entry is present in both indexes but has different values.
+    Mismatched {
+        key: String,
+        local: String,
+        expected: String
+    }
+}
+
+impl std::fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing { key, value } => write!(f, "missing {key}: {value}"),
+            Self::Unexpected { key, value } => write!(f, "unexpected {key}: {value}"),
+
+            Self::Mismatched { key, local, expected } => {
+                write!(f, "mismatched {key}: {local} (expected {expected})")
+            }
+        }
+    }
+}
+
+/// Content of the space index which doesn't depend on the local row IDs, so
+/// indexes of the same space stored in different databases can be compared.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SpaceIndex(BTreeMap<String, String>);
+
+impl SpaceIndex {
+    /// Read index of the provided space.
+    pub fn read(space: &SpaceRecord) -> rusqlite::Result<Self> {
+        let lock = space.database().read();
+
+        let mut entries = BTreeMap::new();
+
+        let mut query = lock.prepare_cached("
+            SELECT public_key FROM users WHERE space_id = ?1
+        ")?;
+
+        for entry in query.query_map([space.id()], |row| {
+            Ok((format!("user {}", read_public_key(row, "public_key")?), String::new()))
+        })? {
+            let (key, value) = entry?;
+
+            entries.insert(key, value);
+        }
+
+        let mut query = lock.prepare_cached("
+            SELECT
+                public_rooms.name,
+                public_rooms.block_hash,
+                public_rooms.transaction_hash,
+                users.public_key
+            FROM public_rooms
+            JOIN users ON users.id = public_rooms.author_id
+            WHERE public_rooms.space_id = ?1
+        ")?;
+
+        for entry in query.query_map([space.id()], |row| {
+            Ok((
+                format!(
+                    "room {}/{}",
+                    read_hash(row, "block_hash")?,
+                    read_hash(row, "transaction_hash")?
+                ),
+                format!(
+                    "#{} by {}",
+                    row.get::<_, String>("name")?,
+                    read_public_key(row, "public_key")?
+                )
+            ))
+        })? {
+            let (key, value) = entry?;
+
+            entries.insert(key, value);
+        }
+
+        let mut query = lock.prepare_cached("
+            SELECT
+                public_rooms.name,
+                public_messages.block_hash,
+                public_messages.transaction_hash,
+                public_messages.timestamp,
+                public_messages.content,
+                users.public_key
+            FROM public_messages
+            JOIN public_rooms ON public_rooms.id = public_messages.room_id
+            JOIN users ON users.id = public_messages.user_id
+            WHERE public_rooms.space_id = ?1
+        ")?;
+
+        for entry in query.query_map([space.id()], |row| {
+            Ok((
+                format!(
+                    "message {}/{}",
+                    read_hash(row, "block_hash")?,
+                    read_hash(row, "transaction_hash")?
+                ),
+                format!(
+                    "#{} by {} at {}: {}",
+                    row.get::<_, String>("name")?,
+                    read_public_key(row, "public_key")?,
+                    row.get::<_, i64>("timestamp")?,
+                    row.get::<_, String>("content")?
+                )
+            ))
+        })? {
+            let (key, value) = entry?;
+
+            entries.insert(key, value);
+        }
+
+        let mut query = lock.prepare_cached("
+            SELECT block_hash, transaction_hash FROM handled_transactions
+            WHERE space_id = ?1
+        ")?;
+
+        for entry in query.query_map([space.id()], |row| {
+            Ok((
+                format!(
+                    "transaction {}/{}",
+                    read_hash(row, "block_hash")?,
+                    read_hash(row, "transaction_hash")?
+                ),
+                String::from("handled")
+            ))
+        })? {
+            let (key, value) = entry?;
+
+            entries.insert(key, value);
+        }
+
+        Ok(Self(entries))
+    }
+
+    /// Amount of entries in the index.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Check if the index has no entries.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Compare current (local) index with the expected one.
+    pub fn compare(&self, expected: &Self) -> Vec<Discrepancy> {
+        let mut discrepancies = Vec::new();
+
+        for (key, value) in &expected.0 {
+            match self.0.get(key) {
+                Some(local) if local == value => (),
+
+                Some(local) => discrepancies.push(Discrepancy::Mismatched {
+                    key: key.clone(),
+                    local: local.clone(),
+                    expected: value.clone()
+                }),
+
+                None => discrepancies.push(Discrepancy::Missing {
+                    key: key.clone(),
+                    value: value.clone()
+                })
+            }
+        }
+
+        for (key, value) in &self.0 {
+            if !expected.0.contains_key(key) {
+                discrepancies.push(Discrepancy::Unexpected {
+                    key: key.clone(),
+                    value: value.clone()
+                });
+            }
+        }
+
+        discrepancies
+    }
+}
+
+#[test]
+fn test_compare() -> anyhow::Result<()> {
+    use super::Database;
+    use super::space::SpaceInfo;
+    use super::user::{UserRecord, UserInfo};
+    use super::public_room::{PublicRoomRecord, PublicRoomInfo};
+    use super::public_message::{PublicRoomMessageRecord, PublicRoomMessageInfo};
+
+    let author = SecretKey::random(&mut crate::utils::get_rng()).public_key();
+
+    let index = |database: Database| -> anyhow::Result<SpaceRecord> {
+        let space = SpaceRecord::create(database.clone(), &SpaceInfo {
+            title: String::new(),
+            root_block: Hash::from([1; 32]),
+            author: author.clone()
+        })?;
+
+        let user = UserRecord::create(database.clone(), &UserInfo {
+            space_id: space.id(),
+            public_key: author.clone(),
+            nickname: None
+        })?;
+
+        let room = PublicRoomRecord::create(database.clone(), &PublicRoomInfo {
+            space_id: space.id(),
+            name: String::from("general"),
+            author_id: user.id(),
+            block_hash: Hash::from([2; 32]),
+            transaction_hash: Hash::from([3; 32])
+        })?;
+
+        PublicRoomMessageRecord::create(database.clone(), &PublicRoomMessageInfo {
+            room_id: room.id(),
+            user_id: user.id(),
+            block_hash: Hash::from([2; 32]),
+            transaction_hash: Hash::from([4; 32]),
+            timestamp: time::UtcDateTime::UNIX_EPOCH,
+            content: String::from("hello world")
+        })?;
+
+        database.mark_handled(space.id(), Hash::from([2; 32]), Hash::from([3; 32]))?;
+        database.mark_handled(space.id(), Hash::from([2; 32]), Hash::from([4; 32]))?;
+
+        Ok(space)
+    };
+
+    let local = index(Database::open_in_memory()?)?;
+    let expected = SpaceIndex::read(&index(Database::open_in_memory()?)?)?;
+
+    assert_eq!(expected.len(), 5);
+    assert!(SpaceIndex::read(&local)?.compare(&expected).is_empty());
+
+    local.clear_index()?;
+
+    let discrepancies = SpaceIndex::read(&local)?.compare(&expected);
+
+    assert_eq!(discrepancies.len(), 5);
+    assert!(discrepancies.iter().all(|discrepancy| matches!(discrepancy, Discrepancy::Missing { .. })));
+
+    Ok(())
+}
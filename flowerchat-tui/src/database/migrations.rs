@@ -0,0 +1,610 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// flowerchat
+// Copyright (C) 2025  Nikita Podvirnyi <krypt0nn@vk.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::path::Path;
+
+use rusqlite::Connection;
+use time::UtcDateTime;
+
+/// Ordered database schema migrations. Migration with index `i` upgrades the
+/// schema from version `i` to version `i + 1`.
+///
+/// Version 0 is used both for new databases and for databases created before
+/// the schema versioning was introduced, so the first migrations must not fail
+/// if their tables already exist.
+const MIGRATIONS: &[&str] = &[
+    // 1: initial schema.
+    r#"
+    CREATE TABLE IF NOT EXISTS spaces (
+        id         INTEGER NOT NULL UNIQUE,
+        title      TEXT,
+        root_block BLOB    NOT NULL,
+        author     BLOB    NOT NULL,
+
+        UNIQUE (root_block),
+
+        PRIMARY KEY (id)
+    );
+
+    CREATE INDEX IF NOT EXISTS spaces_idx ON spaces (
+        id,
+        root_block,
+        author
+    );
+
+    CREATE TABLE IF NOT EXISTS handled_transactions (
+        space_id         INTEGER NOT NULL,
+        block_hash       BLOB    NOT NULL,
+        transaction_hash BLOB    NOT NULL,
+
+        PRIMARY KEY (space_id, block_hash, transaction_hash)
+    );
+
+    CREATE TABLE IF NOT EXISTS shards (
+        space_id INTEGER NOT NULL,
+        address  TEXT    NOT NULL,
+
+        UNIQUE (space_id, address)
+    );
+
+    CREATE INDEX IF NOT EXISTS shards_idx ON shards (space_id);
+
+    CREATE TABLE IF NOT EXISTS users (
+        id         INTEGER NOT NULL UNIQUE,
+        space_id   INTEGER NOT NULL,
+        public_key BLOB    NOT NULL,
+        nickname   TEXT             UNIQUE DEFAULT NULL,
+
+        UNIQUE (space_id, public_key),
+
+        PRIMARY KEY (id),
+        FOREIGN KEY (space_id) REFERENCES spaces (id) ON DELETE CASCADE
+    );
+
+    CREATE INDEX IF NOT EXISTS userd_idx ON users (
+        id,
+        space_id,
+        public_key,
+        nickname
+    );
+
+    CREATE TABLE IF NOT EXISTS public_rooms (
+        id       INTEGER NOT NULL UNIQUE,
+        space_id INTEGER NOT NULL,
+        name     TEXT    NOT NULL,
+
+        author_id        INTEGER NOT NULL,
+        block_hash       BLOB    NOT NULL,
+        transaction_hash BLOB    NOT NULL,
+
+        UNIQUE (space_id, name),
+
+        PRIMARY KEY (id),
+        FOREIGN KEY (space_id)  REFERENCES spaces (id) ON DELETE CASCADE,
+        FOREIGN KEY (author_id) REFERENCES users  (id) ON DELETE CASCADE
+    );
+
+    CREATE INDEX IF NOT EXISTS public_rooms_idx ON public_rooms (
+        id,
+        space_id,
+        name
+    );
+
+    CREATE TABLE IF NOT EXISTS public_messages (
+        id      INTEGER NOT NULL UNIQUE,
+        room_id INTEGER NOT NULL,
+        user_id INTEGER NOT NULL,
+
+        block_hash       BLOB NOT NULL,
+        transaction_hash BLOB NOT NULL,
+
+        timestamp INTEGER NOT NULL,
+        content   TEXT    NOT NULL,
+
+        PRIMARY KEY (id),
+        FOREIGN KEY (room_id)  REFERENCES public_rooms (id) ON DELETE CASCADE,
+        FOREIGN KEY (user_id)  REFERENCES users        (id) ON DELETE CASCADE
+    );
+
+    CREATE INDEX IF NOT EXISTS public_messages_idx ON public_messages (
+        id,
+        room_id,
+        user_id,
+        block_hash,
+        transaction_hash
+    );
+    "#,
+
+    // 2: outgoing transactions queue.
+    r#"
+    CREATE TABLE IF NOT EXISTS outbox (
+        id       INTEGER NOT NULL UNIQUE,
+        space_id INTEGER NOT NULL,
+
+        transaction_hash BLOB NOT NULL,
+        transaction_data BLOB NOT NULL,
+
+        status     INTEGER NOT NULL,
+        block_hash BLOB             DEFAULT NULL,
+        attempts   INTEGER NOT NULL,
+
+        created_at      INTEGER NOT NULL,
+        next_attempt_at INTEGER NOT NULL,
+
+        UNIQUE (space_id, transaction_hash),
+
+        PRIMARY KEY (id),
+        FOREIGN KEY (space_id) REFERENCES spaces (id) ON DELETE CASCADE
+    );
+
+    CREATE INDEX IF NOT EXISTS outbox_idx ON outbox (
+        space_id,
+        status,
+        next_attempt_at
+    );
+    "#,
+
+    // 3: full-text search index over public messages.
+    r#"
+    CREATE VIRTUAL TABLE IF NOT EXISTS public_messages_fts USING fts5 (
+        content,
+
+        content = 'public_messages',
+        content_rowid = 'id'
+    );
+
+    CREATE TRIGGER IF NOT EXISTS public_messages_fts_insert
+    AFTER INSERT ON public_messages BEGIN
+        INSERT INTO public_messages_fts (rowid, content)
+        VALUES (new.id, new.content);
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS public_messages_fts_delete
+    AFTER DELETE ON public_messages BEGIN
+        INSERT INTO public_messages_fts (public_messages_fts, rowid, content)
+        VALUES ('delete', old.id, old.content);
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS public_messages_fts_update
+    AFTER UPDATE OF content ON public_messages BEGIN
+        INSERT INTO public_messages_fts (public_messages_fts, rowid, content)
+        VALUES ('delete', old.id, old.content);
+
+        INSERT INTO public_messages_fts (rowid, content)
+        VALUES (new.id, new.content);
+    END;
+
+    -- Index messages stored before the search index was created.
+    INSERT INTO public_messages_fts (public_messages_fts) VALUES ('rebuild');
+    "#,
+
+    // 4: nicknames are unique within a space, not globally.
+    r#"
+    CREATE TABLE users_new (
+        id         INTEGER NOT NULL UNIQUE,
+        space_id   INTEGER NOT NULL,
+        public_key BLOB    NOT NULL,
+        nickname   TEXT             DEFAULT NULL,
+
+        UNIQUE (space_id, public_key),
+        UNIQUE (space_id, nickname),
+
+        PRIMARY KEY (id),
+        FOREIGN KEY (space_id) REFERENCES spaces (id) ON DELETE CASCADE
+    );
+
+    INSERT INTO users_new (id, space_id, public_key, nickname)
+    SELECT id, space_id, public_key, nickname FROM users;
+
+    DROP TABLE users;
+
+    ALTER TABLE users_new RENAME TO users;
+
+    CREATE INDEX users_idx ON users (
+        id,
+        space_id,
+        public_key,
+        nickname
+    );
+    "#,
+
+    // 5: every transaction can store at most one message.
+    r#"
+    DELETE FROM public_messages WHERE id NOT IN (
+        SELECT MIN(id) FROM public_messages
+        GROUP BY block_hash, transaction_hash
+    );
+
+    CREATE UNIQUE INDEX IF NOT EXISTS public_messages_transaction_idx ON public_messages (
+        block_hash,
+        transaction_hash
+    );
+    "#,
+
+    // 6: time of the last space synchronization.
+    r#"
+    ALTER TABLE spaces ADD COLUMN synced_at INTEGER DEFAULT NULL;
+    "#,
+
+    // 7: shards health tracking.
+    r#"
+    ALTER TABLE shards ADD COLUMN last_seen INTEGER DEFAULT NULL;
+    ALTER TABLE shards ADD COLUMN failures INTEGER NOT NULL DEFAULT 0;
+    "#,
+
+    // 8: author and room of outgoing messages. Messages queued before this
+    // migration are not shown in their rooms until they're included.
+    r#"
+    ALTER TABLE outbox ADD COLUMN author BLOB DEFAULT NULL;
+    ALTER TABLE outbox ADD COLUMN room_name TEXT DEFAULT NULL;
+
+    CREATE INDEX IF NOT EXISTS outbox_room_idx ON outbox (
+        space_id,
+        room_name,
+        status
+    );
+    "#,
+
+    // 9: room messages pagination.
+    r#"
+    CREATE INDEX IF NOT EXISTS public_messages_room_idx ON public_messages (
+        room_id,
+        timestamp,
+        id
+    );
+    "#
+];
+
+/// Amount of migrations which existed before the schema versioning was
+/// introduced. Unversioned databases can only contain their tables.
+#[cfg(test)]
+const UNVERSIONED_MIGRATIONS: u32 = 3;
+
+/// Latest version of the database schema.
+pub const LATEST_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Get schema version of the database.
+pub fn version(connection: &Connection) -> rusqlite::Result<u32> {
+    connection.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+/// Copy the database to a new file next to the original one. Return path to
+/// the created backup.
+pub fn backup(
+    connection: &Connection,
+    path: impl AsRef<Path>
+) -> rusqlite::Result<String> {
+    let version = version(connection)?;
+
+    let backup = format!(
+        "{}.v{version}-{}.bak",
+        path.as_ref().display(),
+        UtcDateTime::now().unix_timestamp()
+    );
+
+    connection.execute("VACUUM INTO ?1", [backup.as_str()])?;
+
+    Ok(backup)
+}
+
+/// Apply all the missing migrations to the database. Every migration runs in
+/// its own transaction together with the schema version update.
+///
+/// If `path` is provided and the database is not empty, its backup is made
+/// before migrating.
+pub fn migrate(
+    connection: &mut Connection,
+    path: Option<&Path>
+) -> anyhow::Result<()> {
+    let current = version(connection)?;
+
+    if current > LATEST_VERSION {
+        anyhow::bail!(
+            "database schema version {current} is newer than supported version {LATEST_VERSION}"
+        );
+    }
+
+    if current == LATEST_VERSION {
+        return Ok(());
+    }
+
+    let is_empty = connection.query_row(
+        "SELECT COUNT(*) FROM sqlite_master",
+        [],
+        |row| row.get::<_, i64>(0)
+    )? == 0;
+
+    if let Some(path) = path && !is_empty {
+        backup(connection, path).map_err(|err| {
+            anyhow::anyhow!(err).context("failed to backup database before migration")
+        })?;
+    }
+
+    // Migrations can re-create tables, which would otherwise cascade deletion
+    // to the tables referencing them. Foreign keys can't be disabled within
+    // a transaction, so they're checked manually before commit instead.
+    let foreign_keys = connection.pragma_query_value(None, "foreign_keys", |row| row.get::<_, bool>(0))?;
+
+    connection.pragma_update(None, "foreign_keys", false)?;
+
+    let result = apply_migrations(connection, current);
+
+    connection.pragma_update(None, "foreign_keys", foreign_keys)?;
+
+    result
+}
+
+fn apply_migrations(connection: &mut Connection, current: u32) -> anyhow::Result<()> {
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(current as usize) {
+        let version = version as u32 + 1;
+
+        let transaction = connection.transaction()?;
+
+        transaction.execute_batch(migration).map_err(|err| {
+            anyhow::anyhow!(err).context(format!(
+                "failed to migrate database to schema version {version}"
+            ))
+        })?;
+
+        let is_consistent = transaction.prepare("PRAGMA foreign_key_check")?
+            .query([])?
+            .next()?
+            .is_none();
+
+        if !is_consistent {
+            anyhow::bail!("foreign key constraint failed after migrating database to schema version {version}");
+        }
+
+        transaction.pragma_update(None, "user_version", version)?;
+        transaction.commit()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+fn legacy_database(version: u32) -> rusqlite::Result<Connection> {
+    let connection = Connection::open_in_memory()?;
+
+    for migration in &MIGRATIONS[..version as usize] {
+        connection.execute_batch(migration)?;
+    }
+
+    connection.execute_batch("
+        INSERT INTO spaces (id, title, root_block, author)
+        VALUES (1, 'test', x'00', x'00');
+
+        INSERT INTO users (id, space_id, public_key, nickname)
+        VALUES (1, 1, x'01', 'alice');
+
+        INSERT INTO shards (space_id, address)
+        VALUES (1, '127.0.0.1:13050');
+
+        INSERT INTO public_rooms (id, space_id, name, author_id, block_hash, transaction_hash)
+        VALUES (1, 1, 'general', 1, x'00', x'00');
+
+        INSERT INTO public_messages (room_id, user_id, block_hash, transaction_hash, timestamp, content)
+        VALUES (1, 1, x'00', x'01', 0, 'hello world');
+    ")?;
+
+    Ok(connection)
+}
+
+#[test]
+fn test_new_database() -> anyhow::Result<()> {
+    let mut connection = Connection::open_in_memory()?;
+
+    migrate(&mut connection, None)?;
+
+    assert_eq!(version(&connection)?, LATEST_VERSION);
+
+    // Migrating already migrated database does nothing.
+    migrate(&mut connection, None)?;
+
+    assert_eq!(version(&connection)?, LATEST_VERSION);
+
+    Ok(())
+}
+
+#[test]
+fn test_legacy_databases() -> anyhow::Result<()> {
+    // Databases created before the versioning have `user_version = 0` but
+    // can already contain any of the tables.
+    let mut databases = (1..=UNVERSIONED_MIGRATIONS)
+        .map(|tables| (tables, 0))
+        .collect::<Vec<_>>();
+
+    databases.extend((1..=LATEST_VERSION).map(|version| (version, version)));
+
+    for (tables, version) in databases {
+        let mut connection = legacy_database(tables)?;
+
+        connection.pragma_update(None, "user_version", version)?;
+
+        migrate(&mut connection, None)?;
+
+        assert_eq!(self::version(&connection)?, LATEST_VERSION);
+
+        let content = connection.query_row(
+            "SELECT content FROM public_messages WHERE id = 1",
+            [],
+            |row| row.get::<_, String>(0)
+        )?;
+
+        assert_eq!(content, "hello world");
+
+        let synced_at = connection.query_row(
+            "SELECT synced_at FROM spaces WHERE id = 1",
+            [],
+            |row| row.get::<_, Option<i64>>(0)
+        )?;
+
+        assert_eq!(synced_at, None);
+
+        let (last_seen, failures) = connection.query_row(
+            "SELECT last_seen, failures FROM shards WHERE space_id = 1",
+            [],
+            |row| Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, u64>(1)?))
+        )?;
+
+        assert_eq!((last_seen, failures), (None, 0));
+
+        // Messages stored before the search index are indexed.
+        let found = connection.query_row(
+            "SELECT rowid FROM public_messages_fts WHERE public_messages_fts MATCH 'hello'",
+            [],
+            |row| row.get::<_, i64>(0)
+        )?;
+
+        assert_eq!(found, 1);
+
+        connection.execute(
+            "INSERT INTO outbox (space_id, transaction_hash, transaction_data, status, attempts, created_at, next_attempt_at)
+             VALUES (1, x'00', x'00', 0, 0, 0, 0)",
+            []
+        )?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_nickname_uniqueness() -> anyhow::Result<()> {
+    let mut connection = legacy_database(UNVERSIONED_MIGRATIONS)?;
+
+    migrate(&mut connection, None)?;
+
+    connection.execute_batch("
+        INSERT INTO spaces (id, title, root_block, author)
+        VALUES (2, 'test 2', x'01', x'00');
+
+        INSERT INTO users (space_id, public_key, nickname)
+        VALUES (2, x'01', 'alice');
+    ")?;
+
+    let result = connection.execute(
+        "INSERT INTO users (space_id, public_key, nickname) VALUES (1, x'02', 'alice')",
+        []
+    );
+
+    assert!(result.is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_duplicated_messages() -> anyhow::Result<()> {
+    let mut connection = legacy_database(4)?;
+
+    connection.execute_batch("
+        INSERT INTO public_messages (room_id, user_id, block_hash, transaction_hash, timestamp, content)
+        VALUES (1, 1, x'00', x'01', 0, 'hello world');
+    ")?;
+
+    connection.pragma_update(None, "user_version", 4)?;
+
+    migrate(&mut connection, None)?;
+
+    let messages = connection.query_row(
+        "SELECT COUNT(*) FROM public_messages",
+        [],
+        |row| row.get::<_, i64>(0)
+    )?;
+
+    assert_eq!(messages, 1);
+
+    let result = connection.execute(
+        "INSERT INTO public_messages (room_id, user_id, block_hash, transaction_hash, timestamp, content)
+         VALUES (1, 1, x'00', x'01', 0, 'hello world')",
+        []
+    );
+
+    assert!(result.is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_newer_database() -> anyhow::Result<()> {
+    let mut connection = Connection::open_in_memory()?;
+
+    connection.pragma_update(None, "user_version", LATEST_VERSION + 1)?;
+
+    assert!(migrate(&mut connection, None).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_backup() -> anyhow::Result<()> {
+    let path = std::env::temp_dir()
+        .join(format!("flowerchat-migrations-test-{}.db", std::process::id()));
+
+    let mut connection = Connection::open(&path)?;
+
+    connection.execute_batch(MIGRATIONS[0])?;
+    connection.pragma_update(None, "user_version", 1)?;
+
+    migrate(&mut connection, Some(&path))?;
+
+    drop(connection);
+
+    let prefix = format!("{}.v1-", path.display());
+
+    let backups = std::fs::read_dir(std::env::temp_dir())?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|entry| entry.to_string_lossy().starts_with(&prefix))
+        .collect::<Vec<_>>();
+
+    assert_eq!(backups.len(), 1);
+
+    let backup = Connection::open(&backups[0])?;
+
+    assert_eq!(version(&backup)?, 1);
+
+    drop(backup);
+
+    std::fs::remove_file(&backups[0])?;
+    std::fs::remove_file(&path)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_messages_pagination_index() -> anyhow::Result<()> {
+    let mut connection = Connection::open_in_memory()?;
+
+    migrate(&mut connection, None)?;
+
+    let plan = connection.prepare("
+        EXPLAIN QUERY PLAN
+        SELECT * FROM public_messages
+        WHERE room_id = 1 AND (timestamp, id) < (2, 3)
+        ORDER BY timestamp DESC, id DESC
+        LIMIT 4
+    ")?
+        .query_map([], |row| row.get::<_, String>("detail"))?
+        .collect::<Result<Vec<_>, _>>()?
+        .join("\n");
+
+    assert!(plan.contains("public_messages_room_idx"), "{plan}");
+    assert!(!plan.contains("TEMP B-TREE"), "{plan}");
+
+    Ok(())
+}
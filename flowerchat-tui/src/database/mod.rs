@@ -18,10 +18,13 @@
 
 use std::path::Path;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::VecDeque;
 use std::iter::FusedIterator;
+use std::ops::Deref;
 
-use spin::{Mutex, MutexGuard};
-use rusqlite::Connection;
+use parking_lot::{Mutex, MutexGuard, ReentrantMutex, ReentrantMutexGuard};
+use rusqlite::{Connection, OpenFlags};
 
 use libflowerpot::crypto::Hash;
 
@@ -29,121 +32,157 @@ pub mod space;
 pub mod user;
 pub mod public_room;
 pub mod public_message;
+pub mod outbox;
+pub mod index;
+pub mod migrations;
+
+/// Amount of rows read by a single query of the database iterators.
+const ITER_PAGE_SIZE: i64 = 64;
+
+/// Amount of read-only connections opened to the database.
+const READERS_POOL_SIZE: usize = 4;
+
+#[derive(Debug)]
+struct Pool {
+    /// The only connection which can modify the database. It can be locked
+    /// multiple times by the same thread so that records can be used within
+    /// a transaction.
+    writer: ReentrantMutex<Connection>,
+
+    /// Read-only connections. Thanks to WAL they can read the database
+    /// while the writer modifies it.
+    readers: Vec<Mutex<Connection>>,
+
+    /// Index of the reader to try first.
+    next_reader: AtomicUsize
+}
+
+/// Connection to the database locked by the current thread.
+enum ConnectionGuard<'a> {
+    Writer(ReentrantMutexGuard<'a, Connection>),
+    Reader(MutexGuard<'a, Connection>)
+}
+
+impl Deref for ConnectionGuard<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Writer(connection) => connection,
+            Self::Reader(connection) => connection
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
-pub struct Database(Arc<Mutex<Connection>>);
+pub struct Database(Arc<Pool>);
 
 impl Database {
-    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
-        let connection = Connection::open(path)?;
-
-        connection.execute_batch(r#"
-            CREATE TABLE IF NOT EXISTS spaces (
-                id         INTEGER NOT NULL UNIQUE,
-                title      TEXT,
-                root_block BLOB    NOT NULL,
-                author     BLOB    NOT NULL,
-
-                UNIQUE (root_block),
-
-                PRIMARY KEY (id)
-            );
-
-            CREATE INDEX IF NOT EXISTS spaces_idx ON spaces (
-                id,
-                root_block,
-                author
-            );
-
-            CREATE TABLE IF NOT EXISTS handled_transactions (
-                space_id         INTEGER NOT NULL,
-                block_hash       BLOB    NOT NULL,
-                transaction_hash BLOB    NOT NULL,
-
-                PRIMARY KEY (space_id, block_hash, transaction_hash)
-            );
-
-            CREATE TABLE IF NOT EXISTS shards (
-                space_id INTEGER NOT NULL,
-                address  TEXT    NOT NULL,
-
-                UNIQUE (space_id, address)
-            );
-
-            CREATE INDEX IF NOT EXISTS shards_idx ON shards (space_id);
-
-            CREATE TABLE IF NOT EXISTS users (
-                id         INTEGER NOT NULL UNIQUE,
-                space_id   INTEGER NOT NULL,
-                public_key BLOB    NOT NULL,
-                nickname   TEXT             UNIQUE DEFAULT NULL,
-
-                UNIQUE (space_id, public_key),
-
-                PRIMARY KEY (id),
-                FOREIGN KEY (space_id) REFERENCES spaces (id) ON DELETE CASCADE
-            );
-
-            CREATE INDEX IF NOT EXISTS userd_idx ON users (
-                id,
-                space_id,
-                public_key,
-                nickname
-            );
-
-            CREATE TABLE IF NOT EXISTS public_rooms (
-                id       INTEGER NOT NULL UNIQUE,
-                space_id INTEGER NOT NULL,
-                name     TEXT    NOT NULL,
-
-                author_id        INTEGER NOT NULL,
-                block_hash       BLOB    NOT NULL,
-                transaction_hash BLOB    NOT NULL,
-
-                UNIQUE (space_id, name),
-
-                PRIMARY KEY (id),
-                FOREIGN KEY (space_id)  REFERENCES spaces (id) ON DELETE CASCADE,
-                FOREIGN KEY (author_id) REFERENCES users  (id) ON DELETE CASCADE
-            );
-
-            CREATE INDEX IF NOT EXISTS public_rooms_idx ON public_rooms (
-                id,
-                space_id,
-                name
-            );
-
-            CREATE TABLE IF NOT EXISTS public_messages (
-                id      INTEGER NOT NULL UNIQUE,
-                room_id INTEGER NOT NULL,
-                user_id INTEGER NOT NULL,
-
-                block_hash       BLOB NOT NULL,
-                transaction_hash BLOB NOT NULL,
-
-                timestamp INTEGER NOT NULL,
-                content   TEXT    NOT NULL,
-
-                PRIMARY KEY (id),
-                FOREIGN KEY (room_id)  REFERENCES public_rooms (id) ON DELETE CASCADE,
-                FOREIGN KEY (user_id)  REFERENCES users        (id) ON DELETE CASCADE
-            );
-
-            CREATE INDEX IF NOT EXISTS public_messages_idx ON public_messages (
-                id,
-                room_id,
-                user_id,
-                block_hash,
-                transaction_hash
-            );
-        "#)?;
-
-        Ok(Self(Arc::new(Mutex::new(connection))))
+    /// Open database and migrate it to the latest schema version.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let mut connection = Connection::open(path.as_ref())?;
+
+        // Allow reading the database while sync loop writes to it.
+        connection.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+        connection.pragma_update(None, "synchronous", "NORMAL")?;
+
+        migrations::migrate(&mut connection, Some(path.as_ref()))?;
+
+        let readers = (0..READERS_POOL_SIZE)
+            .map(|_| {
+                Connection::open_with_flags(
+                    path.as_ref(),
+                    OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX
+                ).map(Mutex::new)
+            })
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(Self(Arc::new(Pool {
+            writer: ReentrantMutex::new(connection),
+            readers,
+            next_reader: AtomicUsize::new(0)
+        })))
     }
 
+    /// Open temporary database stored in memory.
+    pub fn open_in_memory() -> anyhow::Result<Self> {
+        let mut connection = Connection::open_in_memory()?;
+
+        migrations::migrate(&mut connection, None)?;
+
+        // Other connections can't access the same in-memory database so the
+        // writer is used for reading as well.
+        Ok(Self(Arc::new(Pool {
+            writer: ReentrantMutex::new(connection),
+            readers: Vec::new(),
+            next_reader: AtomicUsize::new(0)
+        })))
+    }
+
+    /// Lock the writer connection.
     #[inline]
-    fn lock(&self) -> MutexGuard<'_, Connection> {
-        self.0.lock()
+    fn lock(&self) -> ReentrantMutexGuard<'_, Connection> {
+        self.0.writer.lock()
+    }
+
+    /// Lock a connection which can be used for reading the database.
+    ///
+    /// Writer is returned when current thread is running a transaction so
+    /// that not yet committed changes are visible.
+    fn read(&self) -> ConnectionGuard<'_> {
+        if self.0.readers.is_empty() || self.0.writer.is_owned_by_current_thread() {
+            return ConnectionGuard::Writer(self.lock());
+        }
+
+        let first = self.0.next_reader.fetch_add(1, Ordering::Relaxed);
+        let len = self.0.readers.len();
+
+        for i in 0..len {
+            if let Some(reader) = self.0.readers[(first + i) % len].try_lock() {
+                return ConnectionGuard::Reader(reader);
+            }
+        }
+
+        ConnectionGuard::Reader(self.0.readers[first % len].lock())
+    }
+
+    /// Run provided function in a blocking thread so that it doesn't stall
+    /// the async runtime while waiting for the database.
+    pub async fn spawn<T: Send + 'static>(
+        &self,
+        callback: impl FnOnce(Database) -> anyhow::Result<T> + Send + 'static
+    ) -> anyhow::Result<T> {
+        let database = self.clone();
+
+        tokio::task::spawn_blocking(move || callback(database)).await?
+    }
+
+    /// Run provided function within a database transaction. All the changes
+    /// made by it are rolled back if it returns an error.
+    ///
+    /// Transactions can be nested. Other threads can't modify the database
+    /// until the transaction is finished.
+    pub fn transaction<T>(
+        &self,
+        callback: impl FnOnce() -> anyhow::Result<T>
+    ) -> anyhow::Result<T> {
+        let lock = self.lock();
+
+        lock.execute_batch("SAVEPOINT flowerchat")?;
+
+        match callback() {
+            Ok(result) => {
+                lock.execute_batch("RELEASE flowerchat")?;
+
+                Ok(result)
+            }
+
+            Err(err) => {
+                lock.execute_batch("ROLLBACK TO flowerchat; RELEASE flowerchat")?;
+
+                Err(err)
+            }
+        }
     }
 
     /// Check if transaction with given values is handled.
@@ -156,7 +195,7 @@ impl Database {
         let block_hash: Hash = block_hash.into();
         let transaction_hash: Hash = transaction_hash.into();
 
-        let lock = self.lock();
+        let lock = self.read();
 
         let mut query = lock.prepare_cached("
             SELECT 1 FROM handled_transactions
@@ -207,37 +246,128 @@ impl Database {
     pub fn spaces(&self) -> SpacesIter {
         SpacesIter {
             database: self.clone(),
-            current: 0
+            current: 0,
+            page: VecDeque::new()
         }
     }
 }
 
 pub struct SpacesIter {
     database: Database,
-    current: i64
+    current: i64,
+    page: VecDeque<(i64, rusqlite::Result<space::SpaceInfo>)>
 }
 
 impl Iterator for SpacesIter {
-    type Item = space::SpaceRecord;
+    type Item = (space::SpaceRecord, space::SpaceInfo);
 
     fn next(&mut self) -> Option<Self::Item> {
-        let lock = self.database.lock();
+        let (id, info) = loop {
+            // Read spaces by pages to not to query them one by one.
+            if self.page.is_empty() {
+                let lock = self.database.read();
 
-        let mut query = lock.prepare_cached("
-            SELECT id FROM spaces WHERE id > ?1 ORDER BY id ASC LIMIT 1
-        ").ok()?;
+                let mut query = lock.prepare_cached("
+                    SELECT * FROM spaces WHERE id > ?1 ORDER BY id ASC LIMIT ?2
+                ").ok()?;
+
+                let rows = query.query_map(
+                    [self.current, ITER_PAGE_SIZE],
+                    |row| Ok((row.get("id")?, space::SpaceInfo::from_row(row)))
+                ).ok()?;
 
-        let id = query.query_row([self.current], |row| row.get("id")).ok()?;
+                self.page = rows.filter_map(Result::ok).collect();
+            }
 
-        self.current = id;
+            let (id, info) = self.page.pop_front()?;
+
+            self.current = id;
+
+            // Skip rows which can't be decoded instead of stopping the iterator.
+            if let Ok(info) = info {
+                break (id, info);
+            }
+        };
 
         let record = space::SpaceRecord::open_raw(
             self.database.clone(),
             id
         );
 
-        Some(record)
+        Some((record, info))
     }
 }
 
 impl FusedIterator for SpacesIter {}
+
+#[test]
+fn test_pool() -> anyhow::Result<()> {
+    use std::time::Duration;
+
+    let path = std::env::temp_dir()
+        .join(format!("flowerchat-pool-test-{}.db", std::process::id()));
+
+    let database = Database::open(&path)?;
+
+    database.transaction(|| {
+        database.mark_handled(1, [1; 32], [2; 32])?;
+
+        // Writer thread sees its own not yet committed changes.
+        assert!(database.is_handled(1, [1; 32], [2; 32])?);
+
+        // Readers are not blocked by the open transaction and don't see it.
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        std::thread::spawn({
+            let database = database.clone();
+
+            move || sender.send(database.is_handled(1, [1; 32], [2; 32]).ok())
+        });
+
+        let handled = receiver.recv_timeout(Duration::from_secs(5))?;
+
+        assert_eq!(handled, Some(false));
+
+        Ok(())
+    })?;
+
+    assert!(database.is_handled(1, [1; 32], [2; 32])?);
+
+    drop(database);
+
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_spaces_iter() -> anyhow::Result<()> {
+    use libflowerpot::crypto::SecretKey;
+
+    use crate::utils::get_rng;
+
+    let database = Database::open_in_memory()?;
+
+    let author = SecretKey::random(&mut get_rng()).public_key();
+
+    for i in 1..=3 {
+        space::SpaceRecord::create(database.clone(), &space::SpaceInfo {
+            title: format!("space {i}"),
+            root_block: Hash::from([i; 32]),
+            author: author.clone()
+        })?;
+    }
+
+    // Corrupt author of the second space so it can't be decoded.
+    database.lock().execute("UPDATE spaces SET author = x'00' WHERE id = 2", [])?;
+
+    let titles = database.spaces()
+        .map(|(_, info)| info.title)
+        .collect::<Vec<_>>();
+
+    assert_eq!(titles, ["space 1", "space 3"]);
+
+    Ok(())
+}
@@ -0,0 +1,490 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// flowerchat
+// Copyright (C) 2025  Nikita Podvirnyi <krypt0nn@vk.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashSet;
+
+use anyhow::Context;
+use time::UtcDateTime;
+
+use libflowerpot::crypto::*;
+use libflowerpot::transaction::Transaction;
+
+use super::Database;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OutboxStatus {
+    /// Transaction is stored locally but wasn't announced to any shard yet.
+    Queued,
+
+    /// Transaction was announced to the network shards at least once.
+    Announced,
+
+    /// Transaction was included into the block with the given hash.
+    Included(Hash),
+
+    /// Transaction was not included into the blockchain after all the
+    /// announcement attempts.
+    Failed
+}
+
+impl OutboxStatus {
+    pub const QUEUED: i64    = 0;
+    pub const ANNOUNCED: i64 = 1;
+    pub const INCLUDED: i64  = 2;
+    pub const FAILED: i64    = 3;
+
+    /// Check if the transaction is still waiting to be included into the
+    /// blockchain.
+    #[inline]
+    pub const fn is_pending(&self) -> bool {
+        matches!(self, Self::Queued | Self::Announced)
+    }
+
+    fn to_row(self) -> (i64, Option<[u8; 32]>) {
+        match self {
+            Self::Queued                => (Self::QUEUED, None),
+            Self::Announced             => (Self::ANNOUNCED, None),
+            Self::Included(block_hash)  => (Self::INCLUDED, Some(block_hash.0)),
+            Self::Failed                => (Self::FAILED, None)
+        }
+    }
+
+    fn from_row(status: i64, block_hash: Option<[u8; 32]>) -> Option<Self> {
+        match (status, block_hash) {
+            (Self::QUEUED, _)                    => Some(Self::Queued),
+            (Self::ANNOUNCED, _)                 => Some(Self::Announced),
+            (Self::INCLUDED, Some(block_hash))   => Some(Self::Included(Hash::from(block_hash))),
+            (Self::FAILED, _)                    => Some(Self::Failed),
+
+            _ => None
+        }
+    }
+}
+
+impl std::fmt::Display for OutboxStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Queued               => f.write_str("queued"),
+            Self::Announced            => f.write_str("announced"),
+            Self::Included(block_hash) => write!(f, "included in block {}", block_hash.to_base64()),
+            Self::Failed               => f.write_str("failed")
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OutboxInfo {
+    /// Internal ID of the space this transaction is sent to.
+    pub space_id: i64,
+
+    /// Signed transaction.
+    pub transaction: Transaction,
+
+    /// Public key of the transaction author.
+    pub author: PublicKey,
+
+    /// Name of the public room the transaction sends a message to, if any.
+    pub room_name: Option<String>,
+
+    /// Current status of the transaction.
+    pub status: OutboxStatus,
+
+    /// Amount of times the transaction was announced to the network.
+    pub attempts: u32,
+
+    /// Timestamp of when the transaction was queued.
+    pub created_at: UtcDateTime,
+
+    /// Timestamp after which the transaction should be announced again.
+    pub next_attempt_at: UtcDateTime
+}
+
+/// Outgoing room message which is not included into the blockchain yet.
+#[derive(Debug, Clone)]
+pub struct OutboxMessage {
+    /// Signed transaction of the message.
+    pub transaction: Transaction,
+
+    /// Public key of the message author.
+    pub author: PublicKey,
+
+    /// Current status of the transaction.
+    pub status: OutboxStatus,
+
+    /// Timestamp of when the message was queued.
+    pub created_at: UtcDateTime
+}
+
+#[derive(Debug, Clone)]
+pub struct OutboxRecord(Database, i64);
+
+impl OutboxRecord {
+    /// Create new outgoing transaction record.
+    pub fn create(
+        database: Database,
+        info: &OutboxInfo
+    ) -> rusqlite::Result<Self> {
+        let lock = database.lock();
+
+        let mut query = lock.prepare_cached("
+            INSERT INTO outbox (
+                space_id,
+                transaction_hash,
+                transaction_data,
+                status,
+                block_hash,
+                attempts,
+                created_at,
+                next_attempt_at,
+                author,
+                room_name
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+        ")?;
+
+        let (status, block_hash) = info.status.to_row();
+
+        let id = query.insert((
+            info.space_id,
+            info.transaction.hash().0,
+            info.transaction.to_bytes().as_ref(),
+            status,
+            block_hash,
+            info.attempts,
+            info.created_at.unix_timestamp(),
+            info.next_attempt_at.unix_timestamp(),
+            info.author.to_bytes(),
+            info.room_name.as_deref()
+        ))?;
+
+        drop(query);
+        drop(lock);
+
+        Ok(Self(database, id))
+    }
+
+    /// Open outgoing transaction without verifying its existance.
+    #[inline(always)]
+    pub fn open_raw(database: Database, id: i64) -> Self {
+        Self(database, id)
+    }
+
+    /// Open existing outgoing transaction from its ID.
+    pub fn open(
+        database: Database,
+        id: i64
+    ) -> rusqlite::Result<Self> {
+        database.read()
+            .prepare_cached("SELECT 1 FROM outbox WHERE id = ?1")?
+            .query_row([id], |_| Ok(()))?;
+
+        Ok(Self(database, id))
+    }
+
+    /// Find outgoing transaction from its space ID and hash. Return `None` if
+    /// such transaction doesn't exist.
+    pub fn find(
+        database: Database,
+        space_id: i64,
+        transaction_hash: &Hash
+    ) -> rusqlite::Result<Option<Self>> {
+        let lock = database.read();
+
+        let mut query = lock.prepare_cached("
+            SELECT id FROM outbox WHERE space_id = ?1 AND transaction_hash = ?2
+        ")?;
+
+        let id = query.query_row((
+            space_id, transaction_hash.0
+        ), |row| row.get("id"));
+
+        drop(query);
+        drop(lock);
+
+        match id {
+            Ok(id) => Ok(Some(Self(database, id))),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(err) => Err(err)
+        }
+    }
+
+    /// Get outgoing messages sent to the public room of the space which are
+    /// not included into the blockchain, from the oldest to the newest.
+    pub fn room_messages(
+        database: &Database,
+        space_id: i64,
+        room_name: &str
+    ) -> anyhow::Result<Vec<OutboxMessage>> {
+        let lock = database.read();
+
+        let mut query = lock.prepare_cached("
+            SELECT transaction_data, author, status, created_at FROM outbox
+            WHERE
+                space_id = ?1 AND
+                room_name = ?2 AND
+                status IN (?3, ?4, ?5)
+            ORDER BY id ASC
+        ")?;
+
+        let rows = query.query_map((
+            space_id,
+            room_name,
+            OutboxStatus::QUEUED,
+            OutboxStatus::ANNOUNCED,
+            OutboxStatus::FAILED
+        ), |row| {
+            Ok((
+                row.get::<_, Vec<u8>>("transaction_data")?,
+                row.get::<_, Vec<u8>>("author")?,
+                row.get::<_, i64>("status")?,
+                row.get::<_, i64>("created_at")?
+            ))
+        })?;
+
+        let mut messages = Vec::new();
+
+        for row in rows {
+            let (transaction, author, status, created_at) = row?;
+
+            messages.push(OutboxMessage {
+                transaction: Transaction::from_bytes(transaction)
+                    .context("failed to decode stored transaction")?,
+
+                author: PublicKey::from_bytes(author)
+                    .ok_or_else(|| anyhow::anyhow!("invalid transaction author"))?,
+
+                status: OutboxStatus::from_row(status, None)
+                    .ok_or_else(|| anyhow::anyhow!("invalid transaction status"))?,
+
+                created_at: UtcDateTime::from_unix_timestamp(created_at)?
+            });
+        }
+
+        Ok(messages)
+    }
+
+    /// Get hashes of all the outgoing transactions sent to the public room of
+    /// the space, including the ones already included into the blockchain.
+    pub fn room_transactions(
+        database: &Database,
+        space_id: i64,
+        room_name: &str
+    ) -> rusqlite::Result<HashSet<Hash>> {
+        let lock = database.read();
+
+        let mut query = lock.prepare_cached("
+            SELECT transaction_hash FROM outbox
+            WHERE space_id = ?1 AND room_name = ?2
+        ")?;
+
+        query.query_map((space_id, room_name), |row| {
+            row.get::<_, [u8; 32]>("transaction_hash").map(Hash::from)
+        })?.collect()
+    }
+
+    /// Get list of pending outgoing transactions of the space which should be
+    /// announced to the network at the given time.
+    pub fn due(
+        database: Database,
+        space_id: i64,
+        timestamp: UtcDateTime
+    ) -> rusqlite::Result<Vec<Self>> {
+        let lock = database.read();
+
+        let mut query = lock.prepare_cached("
+            SELECT id FROM outbox
+            WHERE
+                space_id = ?1 AND
+                status IN (?2, ?3) AND
+                next_attempt_at <= ?4
+            ORDER BY id ASC
+        ")?;
+
+        let ids = query.query_map((
+            space_id,
+            OutboxStatus::QUEUED,
+            OutboxStatus::ANNOUNCED,
+            timestamp.unix_timestamp()
+        ), |row| row.get("id"))?.collect::<Result<Vec<i64>, _>>()?;
+
+        drop(query);
+        drop(lock);
+
+        Ok(ids.into_iter()
+            .map(|id| Self(database.clone(), id))
+            .collect())
+    }
+
+    #[inline(always)]
+    pub const fn database(&self) -> &Database {
+        &self.0
+    }
+
+    /// Internal ID of the outgoing transaction.
+    #[inline(always)]
+    pub const fn id(&self) -> i64 {
+        self.1
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> (Database, i64) {
+        (self.0, self.1)
+    }
+
+    /// Internal ID of the space this transaction is sent to.
+    pub fn space_id(&self) -> rusqlite::Result<i64> {
+        self.0.read()
+            .prepare_cached("SELECT space_id FROM outbox WHERE id = ?1")?
+            .query_row([self.1], |row| row.get("space_id"))
+    }
+
+    /// Hash of the transaction.
+    pub fn transaction_hash(&self) -> rusqlite::Result<Hash> {
+        self.0.read()
+            .prepare_cached("SELECT transaction_hash FROM outbox WHERE id = ?1")?
+            .query_row([self.1], |row| row.get::<_, [u8; 32]>("transaction_hash"))
+            .map(Hash::from)
+    }
+
+    /// Signed transaction.
+    pub fn transaction(&self) -> anyhow::Result<Transaction> {
+        let transaction = self.0.read()
+            .prepare_cached("SELECT transaction_data FROM outbox WHERE id = ?1")?
+            .query_row([self.1], |row| row.get::<_, Vec<u8>>("transaction_data"))?;
+
+        Transaction::from_bytes(transaction)
+            .context("failed to decode stored transaction")
+    }
+
+    /// Current status of the transaction.
+    pub fn status(&self) -> rusqlite::Result<OutboxStatus> {
+        self.0.read()
+            .prepare_cached("SELECT status, block_hash FROM outbox WHERE id = ?1")?
+            .query_row([self.1], |row| {
+                Ok((row.get("status")?, row.get("block_hash")?))
+            })
+            .and_then(|(status, block_hash)| {
+                // TODO: better error handling?
+                OutboxStatus::from_row(status, block_hash)
+                    .ok_or_else(|| rusqlite::Error::InvalidQuery)
+            })
+    }
+
+    /// Amount of times the transaction was announced to the network.
+    pub fn attempts(&self) -> rusqlite::Result<u32> {
+        self.0.read()
+            .prepare_cached("SELECT attempts FROM outbox WHERE id = ?1")?
+            .query_row([self.1], |row| row.get("attempts"))
+    }
+
+    /// Timestamp of when the transaction was queued.
+    pub fn created_at(&self) -> rusqlite::Result<UtcDateTime> {
+        self.0.read()
+            .prepare_cached("SELECT created_at FROM outbox WHERE id = ?1")?
+            .query_row([self.1], |row| row.get::<_, i64>("created_at"))
+            .and_then(|timestamp| {
+                UtcDateTime::from_unix_timestamp(timestamp)
+                    .map_err(|_| rusqlite::Error::InvalidQuery)
+            })
+    }
+
+    /// Update status of the current transaction.
+    pub fn update_status(
+        &mut self,
+        status: OutboxStatus
+    ) -> rusqlite::Result<&mut Self> {
+        let (status, block_hash) = status.to_row();
+
+        self.0.lock()
+            .prepare_cached("
+                UPDATE outbox SET status = ?2, block_hash = ?3 WHERE id = ?1
+            ")?
+            .execute((self.1, status, block_hash))?;
+
+        Ok(self)
+    }
+
+    /// Mark queued transaction as announced. Doesn't change status of already
+    /// announced, included or failed transactions.
+    pub fn mark_announced(&mut self) -> rusqlite::Result<&mut Self> {
+        self.0.lock()
+            .prepare_cached("UPDATE outbox SET status = ?2 WHERE id = ?1 AND status = ?3")?
+            .execute((self.1, OutboxStatus::ANNOUNCED, OutboxStatus::QUEUED))?;
+
+        Ok(self)
+    }
+
+    /// Mark pending transaction as failed. Doesn't change status of already
+    /// included transactions.
+    pub fn mark_failed(&mut self) -> rusqlite::Result<&mut Self> {
+        self.0.lock()
+            .prepare_cached("
+                UPDATE outbox SET status = ?2 WHERE id = ?1 AND status IN (?3, ?4)
+            ")?
+            .execute((
+                self.1,
+                OutboxStatus::FAILED,
+                OutboxStatus::QUEUED,
+                OutboxStatus::ANNOUNCED
+            ))?;
+
+        Ok(self)
+    }
+
+    /// Record new announcement attempt of the current transaction and schedule
+    /// the next one.
+    pub fn add_attempt(
+        &mut self,
+        next_attempt_at: UtcDateTime
+    ) -> rusqlite::Result<&mut Self> {
+        self.0.lock()
+            .prepare_cached("
+                UPDATE outbox
+                SET attempts = attempts + 1, next_attempt_at = ?2
+                WHERE id = ?1
+            ")?
+            .execute((self.1, next_attempt_at.unix_timestamp()))?;
+
+        Ok(self)
+    }
+}
+
+#[test]
+fn test_status_rows() {
+    let statuses = [
+        OutboxStatus::Queued,
+        OutboxStatus::Announced,
+        OutboxStatus::Included(Hash::from([1; 32])),
+        OutboxStatus::Failed
+    ];
+
+    for status in statuses {
+        let (value, block_hash) = status.to_row();
+
+        assert_eq!(OutboxStatus::from_row(value, block_hash), Some(status));
+    }
+
+    assert_eq!(OutboxStatus::from_row(OutboxStatus::INCLUDED, None), None);
+    assert_eq!(OutboxStatus::from_row(-1, None), None);
+
+    assert_eq!(OutboxStatus::Queued.to_string(), "queued");
+    assert_eq!(OutboxStatus::Announced.to_string(), "announced");
+    assert_eq!(OutboxStatus::Failed.to_string(), "failed");
+
+    assert!(OutboxStatus::Included(Hash::from([1; 32])).to_string()
+        .starts_with("included in block "));
+}
@@ -41,11 +41,72 @@ pub struct PublicRoomMessageInfo {
     pub content: String
 }
 
+impl PublicRoomMessageInfo {
+    /// Read message info from the `public_messages` table row.
+    pub(super) fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        let timestamp = time::UtcDateTime::from_unix_timestamp(row.get("timestamp")?)
+            .map_err(|_| rusqlite::Error::InvalidQuery)?;
+
+        Ok(Self {
+            room_id: row.get("room_id")?,
+            user_id: row.get("user_id")?,
+            block_hash: Hash::from(row.get::<_, [u8; 32]>("block_hash")?),
+            transaction_hash: Hash::from(row.get::<_, [u8; 32]>("transaction_hash")?),
+            timestamp,
+            content: row.get("content")?
+        })
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct PublicRoomMessagesSearch {
+    /// Words which should be present in the message content.
+    pub text: Option<String>,
+
+    /// Name of the room the message was sent to.
+    pub room: Option<String>,
+
+    /// Nickname or base64 public key of the message sender.
+    pub from: Option<String>,
+
+    /// Return only messages sent before this time.
+    pub before: Option<time::UtcDateTime>,
+
+    /// Return only messages sent after this time.
+    pub after: Option<time::UtcDateTime>
+}
+
+/// Message found by the full-text search with its room and sender.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PublicRoomMessageSearchResult {
+    /// Internal ID of the message.
+    pub id: i64,
+
+    /// Name of the room the message was sent to.
+    pub room_name: String,
+
+    /// Public key of the message sender.
+    pub public_key: PublicKey,
+
+    /// Nickname of the message sender.
+    pub nickname: Option<String>,
+
+    /// Timestamp of when the message was approved by a validator.
+    pub timestamp: time::UtcDateTime,
+
+    /// Content of the message.
+    pub content: String
+}
+
 #[derive(Debug, Clone)]
 pub struct PublicRoomMessageRecord(Database, i64);
 
 impl PublicRoomMessageRecord {
-    /// Create new message record.
+    /// Create new message record. Its content is added to the full-text search
+    /// index by the database trigger.
+    ///
+    /// If the message from the same transaction is already stored then its
+    /// record is returned instead.
     pub fn create(
         database: Database,
         info: &PublicRoomMessageInfo
@@ -53,7 +114,7 @@ impl PublicRoomMessageRecord {
         let lock = database.lock();
 
         let mut query = lock.prepare_cached("
-            INSERT INTO public_messages (
+            INSERT OR IGNORE INTO public_messages (
                 room_id,
                 user_id,
                 block_hash,
@@ -63,7 +124,7 @@ impl PublicRoomMessageRecord {
             ) VALUES (?1, ?2, ?3, ?4, ?5, ?6)
         ")?;
 
-        let id = query.insert((
+        let inserted = query.execute((
             info.room_id,
             info.user_id,
             info.block_hash.0,
@@ -72,6 +133,18 @@ impl PublicRoomMessageRecord {
             info.content.as_str()
         ))?;
 
+        let id = if inserted > 0 {
+            lock.last_insert_rowid()
+        } else {
+            lock.prepare_cached("
+                SELECT id FROM public_messages
+                WHERE block_hash = ?1 AND transaction_hash = ?2
+            ")?.query_row(
+                (info.block_hash.0, info.transaction_hash.0),
+                |row| row.get("id")
+            )?
+        };
+
         drop(query);
         drop(lock);
 
@@ -89,13 +162,98 @@ impl PublicRoomMessageRecord {
         database: Database,
         id: i64
     ) -> rusqlite::Result<Self> {
-        database.lock()
+        database.read()
             .prepare_cached("SELECT 1 FROM public_messages WHERE id = ?1")?
             .query_row([id], |_| Ok(()))?;
 
         Ok(Self(database, id))
     }
 
+    /// Search messages of the space using the full-text search index. Return
+    /// up to `limit` messages from the newest to the oldest.
+    pub fn search(
+        database: Database,
+        space_id: i64,
+        search: &PublicRoomMessagesSearch,
+        limit: usize
+    ) -> rusqlite::Result<Vec<PublicRoomMessageSearchResult>> {
+        // Quote every word so FTS5 doesn't interpret user input as a query
+        // syntax.
+        let text = search.text.as_deref()
+            .map(|text| {
+                text.split_whitespace()
+                    .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            })
+            .filter(|text| !text.is_empty());
+
+        let from_public_key = search.from.as_deref()
+            .and_then(PublicKey::from_base64)
+            .map(|public_key| public_key.to_bytes());
+
+        let lock = database.read();
+
+        let mut query = lock.prepare_cached("
+            SELECT
+                public_messages.id,
+                public_rooms.name,
+                users.public_key,
+                users.nickname,
+                public_messages.timestamp,
+                public_messages.content
+            FROM public_messages
+            JOIN public_rooms ON public_rooms.id = public_messages.room_id
+            JOIN users ON users.id = public_messages.user_id
+            WHERE
+                public_rooms.space_id = ?1 AND
+                (?2 IS NULL OR public_messages.id IN (
+                    SELECT rowid FROM public_messages_fts
+                    WHERE public_messages_fts MATCH ?2
+                )) AND
+                (?3 IS NULL OR public_rooms.name = ?3) AND
+                (?4 IS NULL OR users.nickname = ?4 OR users.public_key = ?5) AND
+                (?6 IS NULL OR public_messages.timestamp < ?6) AND
+                (?7 IS NULL OR public_messages.timestamp > ?7)
+            ORDER BY public_messages.timestamp DESC, public_messages.id DESC
+            LIMIT ?8
+        ")?;
+
+        let rows = query.query_map((
+            space_id,
+            text,
+            search.room.as_deref(),
+            search.from.as_deref(),
+            from_public_key,
+            search.before.map(|before| before.unix_timestamp()),
+            search.after.map(|after| after.unix_timestamp()),
+            limit as i64
+        ), |row| {
+            let public_key = PublicKey::from_bytes(row.get::<_, [u8; 33]>("public_key")?)
+                .ok_or(rusqlite::Error::InvalidQuery)?;
+
+            let timestamp = time::UtcDateTime::from_unix_timestamp(row.get("timestamp")?)
+                .map_err(|_| rusqlite::Error::InvalidQuery)?;
+
+            Ok(PublicRoomMessageSearchResult {
+                id: row.get("id")?,
+                room_name: row.get("name")?,
+                public_key,
+                nickname: row.get("nickname")?,
+                timestamp,
+                content: row.get("content")?
+            })
+        })?;
+
+        let mut messages = Vec::new();
+
+        for row in rows {
+            messages.push(row?);
+        }
+
+        Ok(messages)
+    }
+
     #[inline(always)]
     pub const fn database(&self) -> &Database {
         &self.0
@@ -112,23 +270,30 @@ impl PublicRoomMessageRecord {
         (self.0, self.1)
     }
 
+    /// Read all the message fields with a single query.
+    pub fn load(&self) -> rusqlite::Result<PublicRoomMessageInfo> {
+        self.0.read()
+            .prepare_cached("SELECT * FROM public_messages WHERE id = ?1")?
+            .query_row([self.1], PublicRoomMessageInfo::from_row)
+    }
+
     /// Internal ID of the room.
     pub fn room_id(&self) -> rusqlite::Result<i64> {
-        self.0.lock()
+        self.0.read()
             .prepare_cached("SELECT room_id FROM public_messages WHERE id = ?1")?
             .query_row([self.1], |row| row.get("room_id"))
     }
 
     /// Internal ID of the message sender.
     pub fn user_id(&self) -> rusqlite::Result<i64> {
-        self.0.lock()
+        self.0.read()
             .prepare_cached("SELECT user_id FROM public_messages WHERE id = ?1")?
             .query_row([self.1], |row| row.get("user_id"))
     }
 
     /// Hash of the block where this record is stored.
     pub fn block_hash(&self) -> rusqlite::Result<Hash> {
-        self.0.lock()
+        self.0.read()
             .prepare_cached("SELECT block_hash FROM public_messages WHERE id = ?1")?
             .query_row([self.1], |row| row.get::<_, [u8; 32]>("block_hash"))
             .map(Hash::from)
@@ -136,9 +301,147 @@ impl PublicRoomMessageRecord {
 
     /// Hash of the transaction where this record is stored.
     pub fn transaction_hash(&self) -> rusqlite::Result<Hash> {
-        self.0.lock()
+        self.0.read()
             .prepare_cached("SELECT transaction_hash FROM public_messages WHERE id = ?1")?
             .query_row([self.1], |row| row.get::<_, [u8; 32]>("transaction_hash"))
             .map(Hash::from)
     }
+
+    /// Timestamp of when the message was approved by a validator.
+    pub fn timestamp(&self) -> rusqlite::Result<time::UtcDateTime> {
+        self.0.read()
+            .prepare_cached("SELECT timestamp FROM public_messages WHERE id = ?1")?
+            .query_row([self.1], |row| row.get::<_, i64>("timestamp"))
+            .and_then(|timestamp| {
+                time::UtcDateTime::from_unix_timestamp(timestamp)
+                    .map_err(|_| rusqlite::Error::InvalidQuery)
+            })
+    }
+
+    /// Content of the message.
+    pub fn content(&self) -> rusqlite::Result<String> {
+        self.0.read()
+            .prepare_cached("SELECT content FROM public_messages WHERE id = ?1")?
+            .query_row([self.1], |row| row.get("content"))
+    }
+}
+
+#[test]
+fn test_search() -> anyhow::Result<()> {
+    use super::space::{SpaceRecord, SpaceInfo};
+    use super::user::{UserRecord, UserInfo};
+    use super::public_room::{PublicRoomRecord, PublicRoomInfo};
+
+    let database = Database::open_in_memory()?;
+
+    let author = SecretKey::random(&mut crate::utils::get_rng()).public_key();
+
+    let space = SpaceRecord::create(database.clone(), &SpaceInfo {
+        title: String::new(),
+        root_block: Hash::from([1; 32]),
+        author: author.clone()
+    })?;
+
+    let user = UserRecord::create(database.clone(), &UserInfo {
+        space_id: space.id(),
+        public_key: author.clone(),
+        nickname: Some(String::from("alice"))
+    })?;
+
+    let mut rooms = Vec::new();
+
+    for (i, name) in ["general", "random"].into_iter().enumerate() {
+        rooms.push(PublicRoomRecord::create(database.clone(), &PublicRoomInfo {
+            space_id: space.id(),
+            name: String::from(name),
+            author_id: user.id(),
+            block_hash: Hash::from([i as u8; 32]),
+            transaction_hash: Hash::from([i as u8; 32])
+        })?);
+    }
+
+    let messages = [
+        (0, 10, "hello \"world\""),
+        (0, 20, "NOT this one"),
+        (1, 30, "foo* OR bar"),
+        (1, 40, "hello again")
+    ];
+
+    let mut ids = Vec::new();
+
+    for (i, (room, timestamp, content)) in messages.into_iter().enumerate() {
+        let message = PublicRoomMessageRecord::create(database.clone(), &PublicRoomMessageInfo {
+            room_id: rooms[room].id(),
+            user_id: user.id(),
+            block_hash: Hash::from([i as u8; 32]),
+            transaction_hash: Hash::from([i as u8; 32]),
+            timestamp: time::UtcDateTime::from_unix_timestamp(timestamp)?,
+            content: String::from(content)
+        })?;
+
+        ids.push(message.id());
+    }
+
+    let search = |search: PublicRoomMessagesSearch| -> anyhow::Result<Vec<i64>> {
+        Ok(PublicRoomMessageRecord::search(database.clone(), space.id(), &search, 10)?
+            .into_iter()
+            .map(|message| message.id)
+            .collect())
+    };
+
+    let text = |text: &str| PublicRoomMessagesSearch {
+        text: Some(String::from(text)),
+        ..PublicRoomMessagesSearch::default()
+    };
+
+    // Newest messages first.
+    assert_eq!(search(text("hello"))?, [ids[3], ids[0]]);
+
+    // FTS query syntax is searched as plain words.
+    assert_eq!(search(text("\"world"))?, [ids[0]]);
+    assert_eq!(search(text("NOT"))?, [ids[1]]);
+    assert_eq!(search(text("foo* OR"))?, [ids[2]]);
+    assert!(search(text("hello OR foo"))?.is_empty());
+    assert_eq!(search(text("bar)"))?, [ids[2]]);
+    assert_eq!(search(text("  "))?.len(), ids.len());
+
+    assert_eq!(search(PublicRoomMessagesSearch {
+        room: Some(String::from("random")),
+        ..text("hello")
+    })?, [ids[3]]);
+
+    assert_eq!(search(PublicRoomMessagesSearch {
+        from: Some(String::from("alice")),
+        ..PublicRoomMessagesSearch::default()
+    })?.len(), ids.len());
+
+    assert_eq!(search(PublicRoomMessagesSearch {
+        from: Some(author.to_base64()),
+        ..PublicRoomMessagesSearch::default()
+    })?.len(), ids.len());
+
+    assert!(search(PublicRoomMessagesSearch {
+        from: Some(String::from("bob")),
+        ..PublicRoomMessagesSearch::default()
+    })?.is_empty());
+
+    assert_eq!(search(PublicRoomMessagesSearch {
+        before: Some(time::UtcDateTime::from_unix_timestamp(40)?),
+        after: Some(time::UtcDateTime::from_unix_timestamp(10)?),
+        ..PublicRoomMessagesSearch::default()
+    })?, [ids[2], ids[1]]);
+
+    // Messages stored out of order are sorted by their timestamp.
+    let message = PublicRoomMessageRecord::create(database.clone(), &PublicRoomMessageInfo {
+        room_id: rooms[0].id(),
+        user_id: user.id(),
+        block_hash: Hash::from([4; 32]),
+        transaction_hash: Hash::from([4; 32]),
+        timestamp: time::UtcDateTime::from_unix_timestamp(15)?,
+        content: String::from("hello before")
+    })?;
+
+    assert_eq!(search(text("hello"))?, [ids[3], message.id(), ids[0]]);
+
+    Ok(())
 }
@@ -19,6 +19,7 @@
 use libflowerpot::crypto::*;
 
 use super::Database;
+use super::public_message::PublicRoomMessageInfo;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct PublicRoomInfo {
@@ -38,6 +39,44 @@ pub struct PublicRoomInfo {
     pub transaction_hash: Hash
 }
 
+impl PublicRoomInfo {
+    /// Read room info from the `public_rooms` table row.
+    pub(super) fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            space_id: row.get("space_id")?,
+            name: row.get("name")?,
+            author_id: row.get("author_id")?,
+            block_hash: Hash::from(row.get::<_, [u8; 32]>("block_hash")?),
+            transaction_hash: Hash::from(row.get::<_, [u8; 32]>("transaction_hash")?)
+        })
+    }
+}
+
+/// Position of the room messages page.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PublicRoomMessagesCursor {
+    /// The latest messages of the room.
+    #[default]
+    Latest,
+
+    /// Messages with IDs lower than the provided one.
+    Before(i64),
+
+    /// Messages with IDs greater than the provided one.
+    After(i64),
+
+    /// Messages ordered before the message with provided timestamp and ID.
+    /// Messages of the same block share timestamp, so they're ordered by ID.
+    ///
+    /// Use `i64::MIN` as ID to get messages sent strictly before the time.
+    BeforeTimestamp(time::UtcDateTime, i64),
+
+    /// Messages ordered after the message with provided timestamp and ID.
+    ///
+    /// Use `i64::MAX` as ID to get messages sent strictly after the time.
+    AfterTimestamp(time::UtcDateTime, i64)
+}
+
 #[derive(Debug, Clone)]
 pub struct PublicRoomRecord(Database, i64);
 
@@ -84,7 +123,7 @@ impl PublicRoomRecord {
         database: Database,
         id: i64
     ) -> rusqlite::Result<Self> {
-        database.lock()
+        database.read()
             .prepare_cached("SELECT 1 FROM public_rooms WHERE id = ?1")?
             .query_row([id], |_| Ok(()))?;
 
@@ -98,7 +137,7 @@ impl PublicRoomRecord {
         space_id: i64,
         name: impl AsRef<str>
     ) -> rusqlite::Result<Option<Self>> {
-        let lock = database.lock();
+        let lock = database.read();
 
         let mut query = lock.prepare_cached("
             SELECT id FROM public_rooms WHERE space_id = ?1 AND name = ?2
@@ -134,30 +173,37 @@ impl PublicRoomRecord {
         (self.0, self.1)
     }
 
+    /// Read all the room fields with a single query.
+    pub fn load(&self) -> rusqlite::Result<PublicRoomInfo> {
+        self.0.read()
+            .prepare_cached("SELECT * FROM public_rooms WHERE id = ?1")?
+            .query_row([self.1], PublicRoomInfo::from_row)
+    }
+
     /// Internal ID of the space this room belongs to.
     pub fn space_id(&self) -> rusqlite::Result<i64> {
-        self.0.lock()
+        self.0.read()
             .prepare_cached("SELECT space_id FROM public_rooms WHERE id = ?1")?
             .query_row([self.1], |row| row.get("space_id"))
     }
 
     /// Name of the room.
     pub fn name(&self) -> rusqlite::Result<String> {
-        self.0.lock()
+        self.0.read()
             .prepare_cached("SELECT name FROM public_rooms WHERE id = ?1")?
             .query_row([self.1], |row| row.get("name"))
     }
 
     /// Internal ID of the user who created the room.
     pub fn author_id(&self) -> rusqlite::Result<i64> {
-        self.0.lock()
+        self.0.read()
             .prepare_cached("SELECT author_id FROM public_rooms WHERE id = ?1")?
             .query_row([self.1], |row| row.get("author_id"))
     }
 
     /// Hash of the block where this record is stored.
     pub fn block_hash(&self) -> rusqlite::Result<Hash> {
-        self.0.lock()
+        self.0.read()
             .prepare_cached("SELECT block_hash FROM public_rooms WHERE id = ?1")?
             .query_row([self.1], |row| row.get::<_, [u8; 32]>("block_hash"))
             .map(Hash::from)
@@ -165,7 +211,7 @@ impl PublicRoomRecord {
 
     /// Hash of the transaction where this record is stored.
     pub fn transaction_hash(&self) -> rusqlite::Result<Hash> {
-        self.0.lock()
+        self.0.read()
             .prepare_cached("SELECT transaction_hash FROM public_rooms WHERE id = ?1")?
             .query_row([self.1], |row| row.get::<_, [u8; 32]>("transaction_hash"))
             .map(Hash::from)
@@ -182,4 +228,193 @@ impl PublicRoomRecord {
 
         Ok(self)
     }
+
+    /// Get up to `limit` messages of the current room at the provided
+    /// position, ordered from the oldest to the newest.
+    ///
+    /// Messages are paginated by their IDs (or timestamps and IDs), so to get
+    /// the next page of the scrollback use `Before` with the ID of the first
+    /// returned message.
+    pub fn messages(
+        &self,
+        cursor: PublicRoomMessagesCursor,
+        limit: usize
+    ) -> rusqlite::Result<Vec<(i64, PublicRoomMessageInfo)>> {
+        let (query, params) = match cursor {
+            PublicRoomMessagesCursor::Latest => {
+                return self.messages(
+                    PublicRoomMessagesCursor::BeforeTimestamp(time::UtcDateTime::MAX, i64::MAX),
+                    limit
+                );
+            }
+
+            PublicRoomMessagesCursor::Before(id) => ("
+                SELECT * FROM public_messages
+                WHERE room_id = ?1 AND id < ?2
+                ORDER BY id DESC
+                LIMIT ?3
+            ", vec![self.1, id, limit as i64]),
+
+            PublicRoomMessagesCursor::After(id) => ("
+                SELECT * FROM public_messages
+                WHERE room_id = ?1 AND id > ?2
+                ORDER BY id ASC
+                LIMIT ?3
+            ", vec![self.1, id, limit as i64]),
+
+            PublicRoomMessagesCursor::BeforeTimestamp(timestamp, id) => ("
+                SELECT * FROM public_messages
+                WHERE room_id = ?1 AND (timestamp, id) < (?2, ?3)
+                ORDER BY timestamp DESC, id DESC
+                LIMIT ?4
+            ", vec![self.1, timestamp.unix_timestamp(), id, limit as i64]),
+
+            PublicRoomMessagesCursor::AfterTimestamp(timestamp, id) => ("
+                SELECT * FROM public_messages
+                WHERE room_id = ?1 AND (timestamp, id) > (?2, ?3)
+                ORDER BY timestamp ASC, id ASC
+                LIMIT ?4
+            ", vec![self.1, timestamp.unix_timestamp(), id, limit as i64])
+        };
+
+        let lock = self.0.read();
+
+        let mut query = lock.prepare_cached(query)?;
+
+        let rows = query.query_map(rusqlite::params_from_iter(params), |row| {
+            Ok((row.get("id")?, PublicRoomMessageInfo::from_row(row)?))
+        })?;
+
+        let mut messages = Vec::with_capacity(limit);
+
+        for row in rows {
+            messages.push(row?);
+        }
+
+        // Backward pages are queried from the newest to the oldest.
+        if !matches!(
+            cursor,
+            PublicRoomMessagesCursor::After(_) |
+            PublicRoomMessagesCursor::AfterTimestamp(..)
+        ) {
+            messages.reverse();
+        }
+
+        Ok(messages)
+    }
+}
+
+#[test]
+fn test_messages() -> anyhow::Result<()> {
+    use time::UtcDateTime;
+
+    use super::space::{SpaceRecord, SpaceInfo};
+    use super::user::{UserRecord, UserInfo};
+    use super::public_message::PublicRoomMessageRecord;
+
+    let database = Database::open_in_memory()?;
+
+    let author = SecretKey::random(&mut crate::utils::get_rng()).public_key();
+
+    let space = SpaceRecord::create(database.clone(), &SpaceInfo {
+        title: String::new(),
+        root_block: Hash::from([1; 32]),
+        author: author.clone()
+    })?;
+
+    let user = UserRecord::create(database.clone(), &UserInfo {
+        space_id: space.id(),
+        public_key: author,
+        nickname: None
+    })?;
+
+    let room = PublicRoomRecord::create(database.clone(), &PublicRoomInfo {
+        space_id: space.id(),
+        name: String::from("general"),
+        author_id: user.id(),
+        block_hash: Hash::from([1; 32]),
+        transaction_hash: Hash::from([1; 32])
+    })?;
+
+    // Messages 2, 3 and 4 are stored in the same block.
+    let timestamps = [10, 20, 20, 20, 30];
+
+    let mut ids = Vec::new();
+
+    for (i, timestamp) in timestamps.into_iter().enumerate() {
+        let message = PublicRoomMessageRecord::create(database.clone(), &PublicRoomMessageInfo {
+            room_id: room.id(),
+            user_id: user.id(),
+            block_hash: Hash::from([timestamp as u8; 32]),
+            transaction_hash: Hash::from([i as u8 + 100; 32]),
+            timestamp: UtcDateTime::from_unix_timestamp(timestamp)?,
+            content: format!("message {i}")
+        })?;
+
+        ids.push(message.id());
+    }
+
+    let page_ids = |cursor, limit| -> anyhow::Result<Vec<i64>> {
+        Ok(room.messages(cursor, limit)?.into_iter().map(|(id, _)| id).collect())
+    };
+
+    assert_eq!(page_ids(PublicRoomMessagesCursor::Latest, 2)?, ids[3..]);
+    assert_eq!(page_ids(PublicRoomMessagesCursor::Before(ids[2]), 10)?, ids[..2]);
+    assert_eq!(page_ids(PublicRoomMessagesCursor::After(ids[2]), 10)?, ids[3..]);
+    assert!(page_ids(PublicRoomMessagesCursor::Before(ids[0]), 10)?.is_empty());
+    assert!(page_ids(PublicRoomMessagesCursor::After(ids[4]), 10)?.is_empty());
+
+    // Page backward by one message through messages sharing one timestamp.
+    let mut cursor = PublicRoomMessagesCursor::BeforeTimestamp(UtcDateTime::from_unix_timestamp(30)?, i64::MAX);
+    let mut backward = Vec::new();
+
+    while let [(id, message), ..] = room.messages(cursor, 1)?.as_slice() {
+        backward.push(*id);
+
+        cursor = PublicRoomMessagesCursor::BeforeTimestamp(message.timestamp, *id);
+    }
+
+    backward.reverse();
+
+    assert_eq!(backward, ids);
+
+    // Page forward by two messages.
+    let mut cursor = PublicRoomMessagesCursor::AfterTimestamp(UtcDateTime::from_unix_timestamp(10)?, i64::MIN);
+    let mut forward = Vec::new();
+
+    loop {
+        let page = room.messages(cursor, 2)?;
+
+        let Some((id, message)) = page.last() else {
+            break;
+        };
+
+        cursor = PublicRoomMessagesCursor::AfterTimestamp(message.timestamp, *id);
+
+        forward.extend(page.iter().map(|(id, _)| *id));
+    }
+
+    assert_eq!(forward, ids);
+
+    // Strict time boundaries.
+    let time = UtcDateTime::from_unix_timestamp(20)?;
+
+    assert_eq!(page_ids(PublicRoomMessagesCursor::BeforeTimestamp(time, i64::MIN), 10)?, ids[..1]);
+    assert_eq!(page_ids(PublicRoomMessagesCursor::AfterTimestamp(time, i64::MAX), 10)?, ids[4..]);
+
+    // Latest messages are ordered by time even if stored out of order.
+    let late = PublicRoomMessageRecord::create(database.clone(), &PublicRoomMessageInfo {
+        room_id: room.id(),
+        user_id: user.id(),
+        block_hash: Hash::from([15; 32]),
+        transaction_hash: Hash::from([200; 32]),
+        timestamp: UtcDateTime::from_unix_timestamp(15)?,
+        content: String::from("late message")
+    })?;
+
+    assert_eq!(page_ids(PublicRoomMessagesCursor::Latest, 2)?, ids[3..]);
+    assert_eq!(page_ids(PublicRoomMessagesCursor::Latest, 3)?, [ids[2], ids[3], ids[4]]);
+    assert_eq!(page_ids(PublicRoomMessagesCursor::Latest, 10)?, [ids[0], late.id(), ids[1], ids[2], ids[3], ids[4]]);
+
+    Ok(())
 }
@@ -16,14 +16,19 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::collections::VecDeque;
 use std::iter::FusedIterator;
 
+use time::UtcDateTime;
+
 use libflowerpot::crypto::*;
 
+use flowerchat_protocol::share_link::ShareLink;
+
 use crate::utils::*;
 
-use super::Database;
-use super::public_room::PublicRoomRecord;
+use super::{Database, ITER_PAGE_SIZE};
+use super::public_room::{PublicRoomRecord, PublicRoomInfo};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SpaceInfo {
@@ -37,6 +42,77 @@ pub struct SpaceInfo {
     pub author: PublicKey
 }
 
+impl SpaceInfo {
+    /// Read space info from the `spaces` table row.
+    pub(super) fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        let author = PublicKey::from_bytes(row.get::<_, [u8; 33]>("author")?)
+            .ok_or(rusqlite::Error::InvalidQuery)?;
+
+        Ok(Self {
+            title: row.get::<_, Option<String>>("title")?.unwrap_or_default(),
+            root_block: Hash::from(row.get::<_, [u8; 32]>("root_block")?),
+            author
+        })
+    }
+
+    fn get_space_slice(&self) -> [u8; 65] {
+        let mut slice = [0; 65];
+
+        slice[..32].copy_from_slice(&self.root_block.0);
+        slice[32..].copy_from_slice(&self.author.to_bytes());
+
+        slice
+    }
+
+    /// Get emoji representing the space.
+    #[inline]
+    pub fn emoji(&self) -> &'static str {
+        bytes_to_emoji(self.get_space_slice())
+    }
+
+    /// Get shortname representation of the space.
+    #[inline]
+    pub fn shortname(&self) -> String {
+        bytes_to_shortname(self.get_space_slice())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShardInfo {
+    /// Address of the shard.
+    pub address: String,
+
+    /// Time of the last successful probe of the shard.
+    pub last_seen: Option<UtcDateTime>,
+
+    /// Amount of failed probes in a row.
+    pub failures: u32
+}
+
+impl ShardInfo {
+    /// Read shard info from the `shards` table row.
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        let last_seen = row.get::<_, Option<i64>>("last_seen")?
+            .map(UtcDateTime::from_unix_timestamp)
+            .transpose()
+            .map_err(|_| rusqlite::Error::InvalidQuery)?;
+
+        Ok(Self {
+            address: row.get("address")?,
+            last_seen,
+            failures: row.get("failures")?
+        })
+    }
+}
+
+/// Amount of indexed entries of the space.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SpaceStats {
+    pub rooms: u64,
+    pub users: u64,
+    pub messages: u64
+}
+
 #[derive(Debug, Clone)]
 pub struct SpaceRecord(Database, i64);
 
@@ -79,7 +155,7 @@ impl SpaceRecord {
         database: Database,
         id: i64
     ) -> rusqlite::Result<Self> {
-        database.lock()
+        database.read()
             .prepare_cached("SELECT 1 FROM spaces WHERE id = ?1")?
             .query_row([id], |_| Ok(()))?;
 
@@ -92,7 +168,7 @@ impl SpaceRecord {
         database: Database,
         root_block: &Hash
     ) -> rusqlite::Result<Option<Self>> {
-        let id = database.lock()
+        let id = database.read()
             .prepare_cached("SELECT id FROM spaces WHERE root_block = ?1")?
             .query_row([root_block.0], |row| row.get("id"));
 
@@ -103,6 +179,35 @@ impl SpaceRecord {
         }
     }
 
+    /// Import space from its sharing link. Create the space if it doesn't
+    /// exist, set its title from the link if it's unknown yet and add link's
+    /// shards to the space.
+    pub fn import(
+        database: Database,
+        link: &ShareLink
+    ) -> rusqlite::Result<Self> {
+        let mut space = match Self::find(database.clone(), link.root_block())? {
+            Some(space) => space,
+            None => Self::create(database, &SpaceInfo {
+                title: link.title()
+                    .map(String::from)
+                    .unwrap_or_default(),
+                root_block: *link.root_block(),
+                author: link.public_key().to_owned()
+            })?
+        };
+
+        if let Some(title) = link.title() && space.title()?.is_empty() {
+            space.update_title(title)?;
+        }
+
+        for address in link.shards() {
+            space.add_shard(address)?;
+        }
+
+        Ok(space)
+    }
+
     #[inline(always)]
     pub const fn database(&self) -> &Database {
         &self.0
@@ -119,16 +224,23 @@ impl SpaceRecord {
         (self.0, self.1)
     }
 
+    /// Read all the space fields with a single query.
+    pub fn load(&self) -> rusqlite::Result<SpaceInfo> {
+        self.0.read()
+            .prepare_cached("SELECT * FROM spaces WHERE id = ?1")?
+            .query_row([self.1], SpaceInfo::from_row)
+    }
+
     /// Title of the space.
     pub fn title(&self) -> rusqlite::Result<String> {
-        self.0.lock()
+        self.0.read()
             .prepare_cached("SELECT title FROM spaces WHERE id = ?1")?
             .query_row([self.1], |row| row.get("title"))
     }
 
     /// Hash of the root block of the space's blockchain.
     pub fn root_block(&self) -> rusqlite::Result<Hash> {
-        self.0.lock()
+        self.0.read()
             .prepare_cached("SELECT root_block FROM spaces WHERE id = ?1")?
             .query_row([self.1], |row| row.get::<_, [u8; 32]>("root_block"))
             .map(Hash::from)
@@ -136,7 +248,7 @@ impl SpaceRecord {
 
     /// Public key of the root block's author - creator of the space.
     pub fn author(&self) -> rusqlite::Result<PublicKey> {
-        self.0.lock()
+        self.0.read()
             .prepare_cached("SELECT author FROM spaces WHERE id = ?1")?
             .query_row([self.1], |row| row.get::<_, [u8; 33]>("author"))
             .and_then(|author| {
@@ -158,9 +270,52 @@ impl SpaceRecord {
         Ok(self)
     }
 
+    /// Time when the last block of the space was indexed.
+    pub fn synced_at(&self) -> rusqlite::Result<Option<UtcDateTime>> {
+        let synced_at = self.0.read()
+            .prepare_cached("SELECT synced_at FROM spaces WHERE id = ?1")?
+            .query_row([self.1], |row| row.get::<_, Option<i64>>("synced_at"))?;
+
+        synced_at.map(UtcDateTime::from_unix_timestamp)
+            .transpose()
+            .map_err(|_| rusqlite::Error::InvalidQuery)
+    }
+
+    /// Update time of the last space synchronization.
+    pub fn update_synced_at(&self, synced_at: UtcDateTime) -> rusqlite::Result<()> {
+        self.0.lock()
+            .prepare_cached("UPDATE spaces SET synced_at = ?2 WHERE id = ?1")?
+            .execute((self.1, synced_at.unix_timestamp()))?;
+
+        Ok(())
+    }
+
+    /// Count indexed rooms, users and messages of the space.
+    pub fn stats(&self) -> rusqlite::Result<SpaceStats> {
+        self.0.read()
+            .prepare_cached("
+                SELECT
+                    (SELECT COUNT(*) FROM public_rooms WHERE space_id = ?1) AS rooms,
+                    (SELECT COUNT(*) FROM users WHERE space_id = ?1) AS users,
+                    (
+                        SELECT COUNT(*) FROM public_messages
+                        WHERE room_id IN (
+                            SELECT id FROM public_rooms WHERE space_id = ?1
+                        )
+                    ) AS messages
+            ")?
+            .query_row([self.1], |row| {
+                Ok(SpaceStats {
+                    rooms: row.get("rooms")?,
+                    users: row.get("users")?,
+                    messages: row.get("messages")?
+                })
+            })
+    }
+
     /// List of current space shards.
     pub fn shards(&self) -> rusqlite::Result<Vec<String>> {
-        let lock = self.0.lock();
+        let lock = self.0.read();
 
         let mut query = lock.prepare_cached(
             "SELECT address FROM shards WHERE space_id = ?1"
@@ -188,72 +343,300 @@ impl SpaceRecord {
         Ok(())
     }
 
+    /// Remove shard address from the space. Return `false` if there was no
+    /// such address.
+    pub fn remove_shard(&self, address: impl AsRef<str>) -> rusqlite::Result<bool> {
+        let removed = self.0.lock()
+            .prepare_cached("DELETE FROM shards WHERE space_id = ?1 AND address = ?2")?
+            .execute((self.1, address.as_ref()))?;
+
+        Ok(removed > 0)
+    }
+
+    /// List of current space shards with their health info.
+    pub fn shards_info(&self) -> rusqlite::Result<Vec<ShardInfo>> {
+        let lock = self.0.read();
+
+        let mut query = lock.prepare_cached(
+            "SELECT address, last_seen, failures FROM shards WHERE space_id = ?1"
+        )?;
+
+        let mut shards = Vec::new();
+
+        for shard in query.query_map([self.1], ShardInfo::from_row)? {
+            shards.push(shard?);
+        }
+
+        Ok(shards)
+    }
+
+    /// Store result of the shard probe. Successful probe updates last seen
+    /// time of the shard and resets its failures counter.
+    pub fn record_shard_probe(
+        &self,
+        address: impl AsRef<str>,
+        is_reachable: bool
+    ) -> rusqlite::Result<()> {
+        let lock = self.0.lock();
+
+        if is_reachable {
+            lock.prepare_cached("
+                UPDATE shards SET last_seen = ?3, failures = 0
+                WHERE space_id = ?1 AND address = ?2
+            ")?.execute((self.1, address.as_ref(), UtcDateTime::now().unix_timestamp()))?;
+        } else {
+            lock.prepare_cached("
+                UPDATE shards SET failures = failures + 1
+                WHERE space_id = ?1 AND address = ?2
+            ")?.execute((self.1, address.as_ref()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove all the users, public rooms, messages and handled transactions
+    /// of the current space so they can be indexed from the blockchain again.
+    pub fn clear_index(&self) -> anyhow::Result<()> {
+        self.0.transaction(|| {
+            let lock = self.0.lock();
+
+            lock.prepare_cached("
+                DELETE FROM public_messages WHERE room_id IN (
+                    SELECT id FROM public_rooms WHERE space_id = ?1
+                )
+            ")?.execute([self.1])?;
+
+            lock.prepare_cached("DELETE FROM public_rooms WHERE space_id = ?1")?
+                .execute([self.1])?;
+
+            lock.prepare_cached("DELETE FROM users WHERE space_id = ?1")?
+                .execute([self.1])?;
+
+            lock.prepare_cached("DELETE FROM handled_transactions WHERE space_id = ?1")?
+                .execute([self.1])?;
+
+            Ok(())
+        })
+    }
+
+    /// Remove the space from the database. Its users, public rooms, messages
+    /// and outgoing transactions are removed by the foreign keys cascade.
+    pub fn remove(self) -> anyhow::Result<()> {
+        self.0.transaction(|| {
+            let lock = self.0.lock();
+
+            lock.prepare_cached("DELETE FROM shards WHERE space_id = ?1")?
+                .execute([self.1])?;
+
+            lock.prepare_cached("DELETE FROM handled_transactions WHERE space_id = ?1")?
+                .execute([self.1])?;
+
+            lock.prepare_cached("DELETE FROM spaces WHERE id = ?1")?
+                .execute([self.1])?;
+
+            Ok(())
+        })
+    }
+
     /// Get iterator of all the public rooms existing in the current space.
     #[inline]
     pub fn public_rooms(&self) -> PublicRoomsIter {
         PublicRoomsIter {
             database: self.0.clone(),
             space_id: self.1,
-            current: 0
+            current: 0,
+            page: VecDeque::new()
         }
     }
 
-    fn get_space_slice(&self) -> rusqlite::Result<[u8; 65]> {
-        let root_block = self.root_block()?;
-        let author = self.author()?.to_bytes();
-
-        let mut slice = [0; 65];
-
-        slice[..32].copy_from_slice(&root_block.0);
-        slice[32..].copy_from_slice(&author);
-
-        Ok(slice)
-    }
-
     /// Get emoji representing the current space.
     pub fn emoji(&self) -> rusqlite::Result<&'static str> {
-        Ok(bytes_to_emoji(self.get_space_slice()?))
+        Ok(self.load()?.emoji())
     }
 
     /// Get shortname representation of the current space.
     pub fn shortname(&self) -> rusqlite::Result<String> {
-        Ok(bytes_to_shortname(self.get_space_slice()?))
+        Ok(self.load()?.shortname())
     }
 }
 
 pub struct PublicRoomsIter {
     database: Database,
     space_id: i64,
-    current: i64
+    current: i64,
+    page: VecDeque<(i64, rusqlite::Result<PublicRoomInfo>)>
 }
 
 impl Iterator for PublicRoomsIter {
-    type Item = PublicRoomRecord;
+    type Item = (PublicRoomRecord, PublicRoomInfo);
 
     fn next(&mut self) -> Option<Self::Item> {
-        let lock = self.database.lock();
+        let (id, info) = loop {
+            // Read rooms by pages to not to query them one by one.
+            if self.page.is_empty() {
+                let lock = self.database.read();
 
-        let mut query = lock.prepare_cached("
-            SELECT id FROM public_rooms
-            WHERE space_id = ?1 AND id > ?2
-            ORDER BY id ASC
-            LIMIT 1
-        ").ok()?;
+                let mut query = lock.prepare_cached("
+                    SELECT * FROM public_rooms
+                    WHERE space_id = ?1 AND id > ?2
+                    ORDER BY id ASC
+                    LIMIT ?3
+                ").ok()?;
+
+                let rows = query.query_map(
+                    [self.space_id, self.current, ITER_PAGE_SIZE],
+                    |row| Ok((row.get("id")?, PublicRoomInfo::from_row(row)))
+                ).ok()?;
+
+                self.page = rows.filter_map(Result::ok).collect();
+            }
+
+            let (id, info) = self.page.pop_front()?;
 
-        let id = query.query_row(
-            [self.space_id, self.current],
-            |row| row.get("id")
-        ).ok()?;
+            self.current = id;
 
-        self.current = id;
+            // Skip rows which can't be decoded instead of stopping the iterator.
+            if let Ok(info) = info {
+                break (id, info);
+            }
+        };
 
         let record = PublicRoomRecord::open_raw(
             self.database.clone(),
             id
         );
 
-        Some(record)
+        Some((record, info))
     }
 }
 
 impl FusedIterator for PublicRoomsIter {}
+
+#[test]
+fn test_remove() -> anyhow::Result<()> {
+    use super::user::{UserRecord, UserInfo};
+    use super::public_message::{PublicRoomMessageRecord, PublicRoomMessageInfo};
+
+    let database = Database::open_in_memory()?;
+
+    let author = SecretKey::random(&mut get_rng()).public_key();
+
+    let mut spaces = Vec::new();
+
+    for i in 1..=2 {
+        let space = SpaceRecord::create(database.clone(), &SpaceInfo {
+            title: format!("space {i}"),
+            root_block: Hash::from([i; 32]),
+            author: author.clone()
+        })?;
+
+        let user = UserRecord::create(database.clone(), &UserInfo {
+            space_id: space.id(),
+            public_key: author.clone(),
+            nickname: None
+        })?;
+
+        let room = PublicRoomRecord::create(database.clone(), &PublicRoomInfo {
+            space_id: space.id(),
+            name: String::from("general"),
+            author_id: user.id(),
+            block_hash: Hash::from([i; 32]),
+            transaction_hash: Hash::from([i + 2; 32])
+        })?;
+
+        PublicRoomMessageRecord::create(database.clone(), &PublicRoomMessageInfo {
+            room_id: room.id(),
+            user_id: user.id(),
+            block_hash: Hash::from([i; 32]),
+            transaction_hash: Hash::from([i + 4; 32]),
+            timestamp: UtcDateTime::UNIX_EPOCH,
+            content: String::from("hello world")
+        })?;
+
+        space.add_shard("127.0.0.1:47901")?;
+        space.update_synced_at(UtcDateTime::UNIX_EPOCH)?;
+
+        database.mark_handled(space.id(), Hash::from([i; 32]), Hash::from([i + 2; 32]))?;
+
+        spaces.push(space);
+    }
+
+    let expected = SpaceStats {
+        rooms: 1,
+        users: 1,
+        messages: 1
+    };
+
+    assert_eq!(spaces[0].stats()?, expected);
+    assert_eq!(spaces[0].synced_at()?, Some(UtcDateTime::UNIX_EPOCH));
+
+    let removed = spaces.remove(0);
+    let removed_id = removed.id();
+
+    removed.remove()?;
+
+    let lock = database.lock();
+
+    for table in ["users", "public_rooms", "shards", "handled_transactions"] {
+        let count = lock.query_row(
+            &format!("SELECT COUNT(*) FROM {table} WHERE space_id = ?1"),
+            [removed_id],
+            |row| row.get::<_, u64>(0)
+        )?;
+
+        assert_eq!(count, 0, "{table} rows of the removed space are not deleted");
+    }
+
+    let messages = lock.query_row("SELECT COUNT(*) FROM public_messages", [], |row| row.get::<_, u64>(0))?;
+
+    assert_eq!(messages, 1);
+
+    drop(lock);
+
+    assert!(SpaceRecord::open(database.clone(), removed_id).is_err());
+
+    // Another space is untouched.
+    assert_eq!(spaces[0].stats()?, expected);
+    assert_eq!(spaces[0].shards()?.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_shards() -> anyhow::Result<()> {
+    let database = Database::open_in_memory()?;
+
+    let space = SpaceRecord::create(database, &SpaceInfo {
+        title: String::new(),
+        root_block: Hash::from([1; 32]),
+        author: SecretKey::random(&mut get_rng()).public_key()
+    })?;
+
+    space.add_shard("127.0.0.1:47901")?;
+    space.add_shard("127.0.0.1:47902")?;
+    space.add_shard("127.0.0.1:47901")?;
+
+    space.record_shard_probe("127.0.0.1:47901", false)?;
+    space.record_shard_probe("127.0.0.1:47901", false)?;
+    space.record_shard_probe("127.0.0.1:47902", true)?;
+
+    let mut shards = space.shards_info()?;
+
+    shards.sort_by(|a, b| a.address.cmp(&b.address));
+
+    assert_eq!(shards.len(), 2);
+    assert_eq!((shards[0].failures, shards[0].last_seen), (2, None));
+    assert_eq!(shards[1].failures, 0);
+    assert!(shards[1].last_seen.is_some());
+
+    space.record_shard_probe("127.0.0.1:47901", true)?;
+
+    assert!(space.shards_info()?.iter().all(|shard| shard.failures == 0));
+
+    assert!(space.remove_shard("127.0.0.1:47901")?);
+    assert!(!space.remove_shard("127.0.0.1:47901")?);
+
+    assert_eq!(space.shards()?, [String::from("127.0.0.1:47902")]);
+
+    Ok(())
+}
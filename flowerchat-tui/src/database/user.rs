@@ -32,6 +32,20 @@ pub struct UserInfo {
     pub nickname: Option<String>
 }
 
+impl UserInfo {
+    /// Read user info from the `users` table row.
+    pub(super) fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        let public_key = PublicKey::from_bytes(row.get::<_, [u8; 33]>("public_key")?)
+            .ok_or(rusqlite::Error::InvalidQuery)?;
+
+        Ok(Self {
+            space_id: row.get("space_id")?,
+            public_key,
+            nickname: row.get("nickname")?
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct UserRecord(Database, i64);
 
@@ -74,7 +88,7 @@ impl UserRecord {
         database: Database,
         id: i64
     ) -> rusqlite::Result<Self> {
-        database.lock()
+        database.read()
             .prepare_cached("SELECT 1 FROM users WHERE id = ?1")?
             .query_row([id], |_| Ok(()))?;
 
@@ -88,7 +102,7 @@ impl UserRecord {
         space_id: i64,
         public_key: &PublicKey
     ) -> rusqlite::Result<Option<Self>> {
-        let lock = database.lock();
+        let lock = database.read();
 
         let mut query = lock.prepare_cached("
             SELECT id FROM users WHERE space_id = ?1 AND public_key = ?2
@@ -108,6 +122,33 @@ impl UserRecord {
         }
     }
 
+    /// Find existing user from its space ID and nickname. Return `None` if
+    /// such user doesn't exist.
+    pub fn find_by_nickname(
+        database: Database,
+        space_id: i64,
+        nickname: impl AsRef<str>
+    ) -> rusqlite::Result<Option<Self>> {
+        let lock = database.read();
+
+        let mut query = lock.prepare_cached("
+            SELECT id FROM users WHERE space_id = ?1 AND nickname = ?2
+        ")?;
+
+        let id = query.query_row((
+            space_id, nickname.as_ref()
+        ), |row| row.get("id"));
+
+        drop(query);
+        drop(lock);
+
+        match id {
+            Ok(id) => Ok(Some(Self(database, id))),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(err) => Err(err)
+        }
+    }
+
     #[inline(always)]
     pub const fn database(&self) -> &Database {
         &self.0
@@ -124,16 +165,23 @@ impl UserRecord {
         (self.0, self.1)
     }
 
+    /// Read all the user fields with a single query.
+    pub fn load(&self) -> rusqlite::Result<UserInfo> {
+        self.0.read()
+            .prepare_cached("SELECT * FROM users WHERE id = ?1")?
+            .query_row([self.1], UserInfo::from_row)
+    }
+
     /// Internal ID of the space this user belongs to.
     pub fn space_id(&self) -> rusqlite::Result<i64> {
-        self.0.lock()
+        self.0.read()
             .prepare_cached("SELECT space_id FROM users WHERE id = ?1")?
             .query_row([self.1], |row| row.get("space_id"))
     }
 
     /// Public key of the user.
     pub fn public_key(&self) -> rusqlite::Result<PublicKey> {
-        self.0.lock()
+        self.0.read()
             .prepare_cached("SELECT public_key FROM users WHERE id = ?1")?
             .query_row([self.1], |row| row.get::<_, [u8; 33]>("public_key"))
             .and_then(|public_key| {
@@ -145,7 +193,7 @@ impl UserRecord {
 
     /// Nickname of the user if it's available.
     pub fn nickname(&self) -> rusqlite::Result<Option<String>> {
-        self.0.lock()
+        self.0.read()
             .prepare_cached("SELECT nickname FROM users WHERE id = ?1")?
             .query_row([self.1], |row| row.get("nickname"))
     }
@@ -16,23 +16,148 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::io::Write;
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
 use anyhow::Context;
 use time::UtcDateTime;
 use serde_json::{json, Value as Json};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use rand_chacha::rand_core::RngCore;
+use argon2::{Argon2, Algorithm, Version, Params};
+use chacha20poly1305::{ChaCha20Poly1305, KeyInit, Nonce};
+use chacha20poly1305::aead::Aead;
+use zeroize::Zeroizing;
+use bip39::Mnemonic;
 
 use libflowerpot::crypto::*;
 
 use crate::consts::IDENTITIES_PATH;
-use crate::utils::{bytes_to_emoji, bytes_to_shortname};
+use crate::utils::{get_rng, bytes_to_emoji, bytes_to_shortname, bytes_to_fingerprint};
 
-/// Read identities list from the data folder.
-pub fn read() -> anyhow::Result<Vec<Identity>> {
-    if !IDENTITIES_PATH.exists() {
-        return Ok(vec![]);
+/// Current version of the identities file format.
+///
+/// - `0` - plain JSON array of identities.
+/// - `1` - JSON array of identities encrypted with ChaCha20-Poly1305 using
+///   a key derived from the passphrase with Argon2id.
+pub const FORMAT_VERSION: u64 = 1;
+
+/// Argon2id memory cost in KiB.
+const KDF_MEMORY: u32 = 64 * 1024;
+
+/// Argon2id iterations number.
+const KDF_ITERATIONS: u32 = 3;
+
+/// Argon2id parallelism degree.
+const KDF_PARALLELISM: u32 = 1;
+
+/// Derive encryption key from the passphrase using parameters stored in the
+/// `kdf` field of the identities file.
+fn derive_key(passphrase: &str, kdf: &Json) -> anyhow::Result<Zeroizing<[u8; 32]>> {
+    let field = |name: &str| {
+        kdf.get(name)
+            .and_then(Json::as_u64)
+            .and_then(|value| u32::try_from(value).ok())
+            .ok_or_else(|| anyhow::anyhow!("identities file field 'kdf.{name}' is invalid"))
+    };
+
+    if kdf.get("algorithm").and_then(Json::as_str) != Some("argon2id") {
+        anyhow::bail!("unsupported identities file key derivation algorithm");
     }
 
-    let identities = std::fs::read(IDENTITIES_PATH.as_path())?;
-    let identities = serde_json::from_slice::<Vec<Json>>(&identities)?;
+    let salt = kdf.get("salt")
+        .and_then(Json::as_str)
+        .and_then(|salt| BASE64.decode(salt).ok())
+        .ok_or_else(|| anyhow::anyhow!("identities file field 'kdf.salt' is invalid"))?;
+
+    let params = Params::new(
+        field("memory")?,
+        field("iterations")?,
+        field("parallelism")?,
+        Some(32)
+    ).map_err(|err| anyhow::anyhow!("invalid key derivation parameters: {err}"))?;
+
+    let mut key = Zeroizing::new([0; 32]);
+
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), &salt, key.as_mut())
+        .map_err(|err| anyhow::anyhow!("failed to derive encryption key: {err}"))?;
+
+    Ok(key)
+}
+
+/// Encrypt serialized identities list with the passphrase.
+fn encrypt(identities: &[u8], passphrase: &str) -> anyhow::Result<Json> {
+    let mut rng = get_rng();
+
+    let mut salt = [0; 16];
+    let mut nonce = [0; 12];
+
+    rng.fill_bytes(&mut salt);
+    rng.fill_bytes(&mut nonce);
+
+    let kdf = json!({
+        "algorithm": "argon2id",
+        "memory": KDF_MEMORY,
+        "iterations": KDF_ITERATIONS,
+        "parallelism": KDF_PARALLELISM,
+        "salt": BASE64.encode(salt)
+    });
+
+    let key = derive_key(passphrase, &kdf)?;
+
+    let identities = ChaCha20Poly1305::new(key.as_ref().into())
+        .encrypt(Nonce::from_slice(&nonce), identities)
+        .map_err(|_| anyhow::anyhow!("failed to encrypt identities"))?;
+
+    Ok(json!({
+        "version": FORMAT_VERSION,
+        "kdf": kdf,
+        "cipher": {
+            "algorithm": "chacha20poly1305",
+            "nonce": BASE64.encode(nonce)
+        },
+        "identities": BASE64.encode(identities)
+    }))
+}
+
+/// Decrypt serialized identities list with the passphrase.
+fn decrypt(file: &Json, passphrase: &str) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+    let kdf = file.get("kdf")
+        .ok_or_else(|| anyhow::anyhow!("identities file field 'kdf' is missing"))?;
+
+    let cipher = file.get("cipher")
+        .ok_or_else(|| anyhow::anyhow!("identities file field 'cipher' is missing"))?;
+
+    if cipher.get("algorithm").and_then(Json::as_str) != Some("chacha20poly1305") {
+        anyhow::bail!("unsupported identities file encryption algorithm");
+    }
+
+    let nonce = cipher.get("nonce")
+        .and_then(Json::as_str)
+        .and_then(|nonce| BASE64.decode(nonce).ok())
+        .filter(|nonce| nonce.len() == 12)
+        .ok_or_else(|| anyhow::anyhow!("identities file field 'cipher.nonce' is invalid"))?;
+
+    let identities = file.get("identities")
+        .and_then(Json::as_str)
+        .and_then(|identities| BASE64.decode(identities).ok())
+        .ok_or_else(|| anyhow::anyhow!("identities file field 'identities' is invalid"))?;
+
+    let key = derive_key(passphrase, kdf)?;
+
+    let identities = ChaCha20Poly1305::new(key.as_ref().into())
+        .decrypt(Nonce::from_slice(&nonce), identities.as_slice())
+        .map_err(|_| anyhow::anyhow!("invalid passphrase"))?;
+
+    Ok(Zeroizing::new(identities))
+}
+
+fn parse(identities: &[u8]) -> anyhow::Result<Vec<Identity>> {
+    let identities = serde_json::from_slice::<Vec<Json>>(identities)?;
 
     let mut identities = identities.into_iter()
         .map(|identity| {
@@ -41,27 +166,271 @@ pub fn read() -> anyhow::Result<Vec<Identity>> {
         })
         .collect::<Result<Vec<_>, _>>()?;
 
-    identities.dedup_by(|a, b| a.secret_key() == b.secret_key());
+    // Compare public keys so secret keys are not copied around.
+    let mut known = HashSet::new();
+
+    identities.retain(|identity| known.insert(identity.public_key().to_bytes()));
 
     Ok(identities)
 }
 
-/// Write identities list to the data folder.
+/// Check if identities file exists in the data folder.
+#[inline]
+pub fn exists() -> bool {
+    IDENTITIES_PATH.exists()
+}
+
+/// Check if identities file exists in the data folder and is encrypted with
+/// a passphrase. Plaintext files of older format version are not.
+#[inline]
+pub fn is_encrypted() -> bool {
+    is_encrypted_at(IDENTITIES_PATH.as_path())
+}
+
+fn is_encrypted_at(path: &Path) -> bool {
+    std::fs::read(path).ok()
+        .and_then(|file| serde_json::from_slice::<Json>(&file).ok())
+        .is_some_and(|file| file.get("version").is_some())
+}
+
+/// Read identities list from the data folder and decrypt it with the
+/// passphrase.
+///
+/// Plaintext files of older format version are encrypted with the provided
+/// passphrase.
+#[inline]
+pub fn read(passphrase: &str) -> anyhow::Result<Vec<Identity>> {
+    read_from(IDENTITIES_PATH.as_path(), passphrase)
+}
+
+fn read_from(path: &Path, passphrase: &str) -> anyhow::Result<Vec<Identity>> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+
+    let file = std::fs::read(path)?;
+    let file = serde_json::from_slice::<Json>(&file)?;
+
+    match file.get("version").and_then(Json::as_u64) {
+        Some(FORMAT_VERSION) => parse(&decrypt(&file, passphrase)?),
+
+        Some(version) => anyhow::bail!("unsupported identities file version: {version}"),
+
+        None => {
+            let identities = parse(&serde_json::to_vec(&file)?)?;
+
+            write_to(path, identities.clone(), passphrase)
+                .context("failed to encrypt identities file")?;
+
+            // Make sure the encrypted file can be decrypted back before
+            // reporting success since the plaintext file is gone now.
+            let file = std::fs::read(path)?;
+            let file = serde_json::from_slice::<Json>(&file)?;
+
+            if parse(&decrypt(&file, passphrase)?)? != identities {
+                anyhow::bail!("encrypted identities file doesn't match the plaintext one");
+            }
+
+            Ok(identities)
+        }
+    }
+}
+
+/// Encrypt identities list with the passphrase and write it to the data
+/// folder.
+#[inline]
 pub fn write(
-    identities: impl IntoIterator<Item = Identity>
+    identities: impl IntoIterator<Item = Identity>,
+    passphrase: &str
+) -> anyhow::Result<()> {
+    write_to(IDENTITIES_PATH.as_path(), identities, passphrase)
+}
+
+fn write_to(
+    path: &Path,
+    identities: impl IntoIterator<Item = Identity>,
+    passphrase: &str
 ) -> anyhow::Result<()> {
     let identities = identities.into_iter()
         .map(|identity| identity.to_json())
         .collect::<Vec<_>>();
 
-    std::fs::write(
-        IDENTITIES_PATH.as_path(),
-        serde_json::to_vec_pretty(&json!(identities))?
-    )?;
+    let identities = Zeroizing::new(serde_json::to_vec(&json!(identities))?);
+
+    write_atomically(
+        path,
+        &serde_json::to_vec_pretty(&encrypt(&identities, passphrase)?)?
+    )
+}
+
+/// Write file contents to a temporary file next to it and replace the file
+/// by it, so the file is never left partially written.
+fn write_atomically(path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+    let mut temp_path = path.as_os_str().to_owned();
+
+    temp_path.push(".tmp");
+
+    let mut file = std::fs::File::create(&temp_path)
+        .context("failed to create temporary file")?;
+
+    file.write_all(contents)?;
+    file.sync_all()?;
+
+    drop(file);
+
+    std::fs::rename(&temp_path, path)
+        .context("failed to replace file")?;
+
+    // Persist the rename itself.
+    if let Some(parent) = path.parent() &&
+        let Ok(parent) = std::fs::File::open(parent)
+    {
+        let _ = parent.sync_all();
+    }
 
     Ok(())
 }
 
+/// Find identity by its index (starting from 1), title or shortname. Return
+/// index of the found identity in the list.
+pub fn find(identities: &[Identity], query: &str) -> Option<usize> {
+    if let Ok(index) = query.parse::<usize>() &&
+        (1..=identities.len()).contains(&index)
+    {
+        return Some(index - 1);
+    }
+
+    identities.iter()
+        .position(|identity| identity.title() == query)
+        .or_else(|| {
+            identities.iter()
+                .position(|identity| identity.shortname() == query)
+        })
+}
+
+/// Encode secret key into a BIP39 mnemonic phrase of 24 words.
+pub fn secret_key_to_mnemonic(secret_key: &SecretKey) -> anyhow::Result<String> {
+    let secret_key = Zeroizing::new(secret_key.to_bytes());
+
+    let mnemonic = Mnemonic::from_entropy(secret_key.as_ref())
+        .map_err(|err| anyhow::anyhow!("failed to encode mnemonic phrase: {err}"))?;
+
+    Ok(mnemonic.to_string())
+}
+
+/// Decode secret key from a BIP39 mnemonic phrase verifying its checksum.
+pub fn secret_key_from_mnemonic(phrase: &str) -> anyhow::Result<SecretKey> {
+    let mnemonic = Mnemonic::parse_normalized(phrase)
+        .map_err(|err| anyhow::anyhow!("invalid mnemonic phrase: {err}"))?;
+
+    let entropy = Zeroizing::new(mnemonic.to_entropy());
+
+    let secret_key = <&[u8; 32]>::try_from(entropy.as_slice())
+        .map_err(|_| anyhow::anyhow!("mnemonic phrase must consist of 24 words"))?;
+
+    SecretKey::from_bytes(secret_key)
+        .ok_or_else(|| anyhow::anyhow!("mnemonic phrase doesn't encode a valid secret key"))
+}
+
+/// Identities list decrypted with the passphrase and kept in memory until
+/// the timeout.
+#[derive(Default, Clone)]
+pub struct UnlockedIdentities {
+    unlocked: Option<(Vec<Identity>, Zeroizing<String>, Instant)>,
+    timeout: Option<Duration>
+}
+
+impl UnlockedIdentities {
+    /// Forget unlocked identities after the provided duration. They're kept
+    /// until the app is closed if timeout is not set.
+    #[inline]
+    pub fn new(timeout: Option<Duration>) -> Self {
+        Self {
+            unlocked: None,
+            timeout
+        }
+    }
+
+    /// Read and decrypt identities list from the data folder.
+    pub fn unlock(&mut self, passphrase: impl Into<String>) -> anyhow::Result<()> {
+        let passphrase = Zeroizing::new(passphrase.into());
+        let identities = read(&passphrase)?;
+
+        self.unlocked = Some((identities, passphrase, Instant::now()));
+
+        Ok(())
+    }
+
+    /// Encrypt updated identities list with the passphrase used to unlock
+    /// it and write it to the data folder.
+    pub fn update(&mut self, identities: Vec<Identity>) -> anyhow::Result<()> {
+        if !self.is_unlocked() {
+            anyhow::bail!("identities are locked");
+        }
+
+        let Some((unlocked, passphrase, _)) = &mut self.unlocked else {
+            anyhow::bail!("identities are locked");
+        };
+
+        write(identities.clone(), passphrase)?;
+
+        *unlocked = identities;
+
+        Ok(())
+    }
+
+    /// Forget unlocked identities.
+    #[inline]
+    pub fn lock(&mut self) {
+        self.unlocked = None;
+    }
+
+    /// Forget unlocked identities if the timeout has passed. Return `true`
+    /// if identities were forgotten.
+    pub fn lock_expired(&mut self) -> bool {
+        if self.unlocked.is_some() && !self.is_unlocked() {
+            self.lock();
+
+            return true;
+        }
+
+        false
+    }
+
+    /// Check if identities are unlocked.
+    pub fn is_unlocked(&self) -> bool {
+        match (&self.unlocked, self.timeout) {
+            (Some((_, _, unlocked_at)), Some(timeout)) => unlocked_at.elapsed() < timeout,
+            (Some(_), None) => true,
+            (None, _) => false
+        }
+    }
+
+    /// Get unlocked identities list. Return `None` if identities are locked.
+    pub fn identities(&self) -> Option<&[Identity]> {
+        if !self.is_unlocked() {
+            return None;
+        }
+
+        self.unlocked.as_ref()
+            .map(|(identities, _, _)| identities.as_slice())
+    }
+
+    #[inline(always)]
+    pub const fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+}
+
+impl std::fmt::Debug for UnlockedIdentities {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UnlockedIdentities")
+            .field("is_unlocked", &self.is_unlocked())
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
 /// Identity is a cross-space profile which can be used by the user. It has a
 /// user-defined title for easier navigation and a secret key.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -88,6 +457,11 @@ impl Identity {
         &self.title
     }
 
+    #[inline]
+    pub fn set_title(&mut self, title: impl ToString) {
+        self.title = title.to_string();
+    }
+
     #[inline(always)]
     pub const fn secret_key(&self) -> &SecretKey {
         &self.secret_key
@@ -98,16 +472,29 @@ impl Identity {
         &self.created_at
     }
 
-    /// Get emoji representing the current identity.
+    #[inline]
+    pub fn public_key(&self) -> PublicKey {
+        self.secret_key.public_key()
+    }
+
+    /// Get emoji representing the current identity. It's the same emoji
+    /// other users see next to its messages.
     #[inline]
     pub fn emoji(&self) -> &'static str {
-        bytes_to_emoji(self.secret_key.to_bytes())
+        bytes_to_emoji(self.public_key().to_bytes())
     }
 
-    /// Get shortname representation of the current identity.
+    /// Get shortname representation of the current identity. It's the same
+    /// shortname other users see if no nickname is set.
     #[inline]
     pub fn shortname(&self) -> String {
-        bytes_to_shortname(self.secret_key.to_bytes())
+        bytes_to_shortname(self.public_key().to_bytes())
+    }
+
+    /// Get fingerprint of the current identity's public key.
+    #[inline]
+    pub fn fingerprint(&self) -> String {
+        bytes_to_fingerprint(self.public_key().to_bytes())
     }
 
     pub fn to_json(&self) -> Json {
@@ -137,3 +524,104 @@ impl Identity {
         })
     }
 }
+
+#[test]
+fn test_encryption() -> anyhow::Result<()> {
+    let identities = b"[]";
+
+    let file = encrypt(identities, "passphrase")?;
+
+    assert_eq!(file.get("version").and_then(Json::as_u64), Some(FORMAT_VERSION));
+    assert_eq!(decrypt(&file, "passphrase")?.as_slice(), identities);
+    assert!(decrypt(&file, "wrong passphrase").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_mnemonic() -> anyhow::Result<()> {
+    let secret_key = SecretKey::from_bytes(&[1; 32]).unwrap();
+
+    let phrase = secret_key_to_mnemonic(&secret_key)?;
+
+    assert_eq!(phrase.split(' ').count(), 24);
+    assert_eq!(secret_key_from_mnemonic(&phrase)?, secret_key);
+
+    // Swapping words breaks the checksum.
+    let mut words = phrase.split(' ').collect::<Vec<_>>();
+
+    words.swap(0, 23);
+
+    assert!(secret_key_from_mnemonic(&words.join(" ")).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_write_atomically() -> anyhow::Result<()> {
+    let path = std::env::temp_dir()
+        .join(format!("flowerchat-identities-{}.json", get_rng().next_u64()));
+
+    write_atomically(&path, b"old")?;
+    write_atomically(&path, b"new")?;
+
+    let mut temp_path = path.as_os_str().to_owned();
+
+    temp_path.push(".tmp");
+
+    assert_eq!(std::fs::read(&path)?, b"new");
+    assert!(!Path::new(&temp_path).exists());
+
+    std::fs::remove_file(path)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_duplicates() -> anyhow::Result<()> {
+    let identity = |title: &str, secret_key: u8| json!({
+        "title": title,
+        "secret_key": SecretKey::from_bytes(&[secret_key; 32]).unwrap().to_base64(),
+        "created_at": 0
+    });
+
+    let identities = parse(&serde_json::to_vec(&json!([
+        identity("alice", 1),
+        identity("bob", 2),
+        identity("alice again", 1)
+    ]))?)?;
+
+    let titles = identities.iter()
+        .map(Identity::title)
+        .collect::<Vec<_>>();
+
+    assert_eq!(titles, ["alice", "bob"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_legacy_migration() -> anyhow::Result<()> {
+    let path = std::env::temp_dir()
+        .join(format!("flowerchat-identities-{}.json", get_rng().next_u64()));
+
+    let identity = Identity::from_json(&json!({
+        "title": "alice",
+        "secret_key": SecretKey::from_bytes(&[1; 32]).unwrap().to_base64(),
+        "created_at": 0
+    }))?;
+
+    std::fs::write(&path, serde_json::to_vec(&json!([identity.to_json()]))?)?;
+
+    assert!(!is_encrypted_at(&path));
+
+    // Plaintext file is replaced by the encrypted one.
+    assert_eq!(read_from(&path, "passphrase")?, vec![identity.clone()]);
+    assert!(is_encrypted_at(&path));
+    assert!(read_from(&path, "wrong passphrase").is_err());
+    assert_eq!(read_from(&path, "passphrase")?, vec![identity]);
+
+    std::fs::remove_file(path)?;
+
+    Ok(())
+}
@@ -23,11 +23,13 @@ use std::io::{Read, Write};
 use std::path::PathBuf;
 use std::net::{SocketAddr, Ipv6Addr};
 use std::fs::File;
+use std::time::Duration;
 
 use anyhow::Context;
 use clap::{Parser, Subcommand};
 use tokio::runtime::Handle;
 use rand_chacha::rand_core::RngCore;
+use zeroize::Zeroizing;
 
 use libflowerpot::crypto::*;
 use libflowerpot::block::{Block, BlockContent};
@@ -35,6 +37,7 @@ use libflowerpot::storage::Storage;
 use libflowerpot::storage::sqlite_storage::SqliteStorage;
 use libflowerpot::client::Client;
 use libflowerpot::pool::ShardsPool;
+use libflowerpot::viewer::Viewer;
 use libflowerpot::security::SecurityRules;
 use libflowerpot::shard::{Shard, ShardSettings, serve as serve_shard};
 use libflowerpot::validator::{
@@ -47,12 +50,19 @@ pub mod consts;
 pub mod utils;
 pub mod database;
 pub mod identities;
+pub mod signature;
 pub mod client;
+pub mod outbox;
+pub mod shards;
 pub mod validator;
 pub mod tui;
 
 use database::Database;
-use database::space::{SpaceRecord, SpaceInfo};
+use identities::Identity;
+use signature::DetachedSignature;
+use database::space::SpaceRecord;
+use database::index::SpaceIndex;
+use client::HandlerEvent;
 
 #[derive(Parser)]
 #[command(version)]
@@ -61,6 +71,10 @@ struct Cli {
     #[arg(long, alias = "log")]
     debug: Option<PathBuf>,
 
+    /// Forget unlocked identities after provided amount of seconds.
+    #[arg(long)]
+    identities_timeout: Option<u64>,
+
     #[command(subcommand)]
     command: Option<Command>
 }
@@ -73,6 +87,12 @@ enum Command {
         command: KeypairCommand
     },
 
+    /// Identities management tools.
+    Identity {
+        #[command(subcommand)]
+        command: IdentityCommand
+    },
+
     /// Spaces management tools.
     Space {
         #[command(subcommand)]
@@ -85,6 +105,7 @@ impl Command {
     pub async fn run(self, database: Database) -> anyhow::Result<()> {
         match self {
             Self::Keypair { command } => command.run().await,
+            Self::Identity { command } => command.run().await,
             Self::Space { command } => command.run(database).await
         }
     }
@@ -102,6 +123,83 @@ enum KeypairCommand {
     Export {
         #[arg(short, long)]
         secret_key: Option<String>
+    },
+
+    /// Encode the provided secret key into a BIP39 mnemonic phrase.
+    ///
+    /// If `secret_key` argument is not specified then stdin value will be used
+    /// as input.
+    ExportMnemonic {
+        #[arg(short, long)]
+        secret_key: Option<String>
+    },
+
+    /// Restore secret key from its BIP39 mnemonic phrase.
+    ///
+    /// If `phrase` argument is not specified then stdin value will be used
+    /// as input.
+    ImportMnemonic {
+        #[arg(short, long)]
+        phrase: Option<String>
+    },
+
+    /// Sign file content with the secret key and print the detached signature
+    /// in a text armor format.
+    ///
+    /// If `path` argument is not specified then stdin value will be signed.
+    Sign {
+        /// Secret key or identity (index, title or shortname) of the signer.
+        #[arg(short = 'k', long)]
+        secret_key: String,
+
+        /// Path to the signed file.
+        path: Option<PathBuf>
+    },
+
+    /// Verify detached signature of the file content.
+    ///
+    /// If `path` argument is not specified then stdin value will be verified.
+    Verify {
+        /// Path to the armored signature file.
+        #[arg(short, long)]
+        signature: PathBuf,
+
+        /// Expected public key of the signer.
+        #[arg(short, long)]
+        public_key: Option<String>,
+
+        /// Path to the signed file.
+        path: Option<PathBuf>
+    }
+}
+
+/// Read file content or stdin if path is not provided.
+fn file_or_stdin(path: Option<PathBuf>) -> anyhow::Result<Vec<u8>> {
+    match path {
+        Some(path) => std::fs::read(&path)
+            .with_context(|| format!("failed to read file: {path:?}")),
+
+        None => {
+            let mut content = Vec::new();
+
+            std::io::stdin().read_to_end(&mut content)?;
+
+            Ok(content)
+        }
+    }
+}
+
+/// Return provided argument value or read it from stdin.
+fn arg_or_stdin(value: Option<String>) -> anyhow::Result<Zeroizing<String>> {
+    match value {
+        Some(value) => Ok(Zeroizing::new(value)),
+        None => {
+            let mut value = Zeroizing::new(String::new());
+
+            std::io::stdin().read_to_string(&mut value)?;
+
+            Ok(value)
+        }
     }
 }
 
@@ -146,6 +244,301 @@ impl KeypairCommand {
                 stdout.write_all(public_key.to_base64().as_bytes())?;
                 stdout.flush()?;
             }
+
+            Self::ExportMnemonic { secret_key } => {
+                let secret_key = arg_or_stdin(secret_key)?;
+
+                let secret_key = SecretKey::from_base64(secret_key.trim())
+                    .ok_or_else(|| anyhow::anyhow!("failed to decode secret key"))?;
+
+                let phrase = Zeroizing::new(identities::secret_key_to_mnemonic(&secret_key)?);
+
+                let mut stdout = std::io::stdout();
+
+                stdout.write_all(phrase.as_bytes())?;
+                stdout.flush()?;
+            }
+
+            Self::Sign { secret_key, path } => {
+                let secret_key = find_secret_key(&secret_key)?;
+
+                let message = file_or_stdin(path)?;
+
+                let signature = DetachedSignature::create(&secret_key, message)?;
+
+                let mut stdout = std::io::stdout();
+
+                stdout.write_all(signature.to_armor().as_bytes())?;
+                stdout.flush()?;
+            }
+
+            Self::Verify { signature, public_key, path } => {
+                let signature = std::fs::read_to_string(&signature)
+                    .with_context(|| format!("failed to read signature file: {signature:?}"))?;
+
+                let signature = DetachedSignature::from_armor(signature)
+                    .context("invalid signature format")?;
+
+                if let Some(public_key) = public_key {
+                    let public_key = PublicKey::from_base64(public_key)
+                        .ok_or_else(|| anyhow::anyhow!("invalid public key format"))?;
+
+                    if &public_key != signature.public_key() {
+                        anyhow::bail!("message is signed by another public key: {}", signature.public_key().to_base64());
+                    }
+                }
+
+                let message = file_or_stdin(path)?;
+
+                if !signature.verify(message)? {
+                    anyhow::bail!("invalid signature");
+                }
+
+                println!("Valid signature by {}", signature.public_key().to_base64());
+            }
+
+            Self::ImportMnemonic { phrase } => {
+                let phrase = arg_or_stdin(phrase)?;
+
+                let secret_key = identities::secret_key_from_mnemonic(&phrase)?;
+
+                let mut stdout = std::io::stdout();
+
+                stdout.write_all(secret_key.to_base64().as_bytes())?;
+                stdout.flush()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Ask user for the identities file passphrase. The passphrase is asked twice
+/// if the file is about to be encrypted: when it's not encrypted yet, or when
+/// it doesn't exist and `create` is set.
+fn read_passphrase(create: bool) -> anyhow::Result<Zeroizing<String>> {
+    let passphrase = Zeroizing::new(rpassword::prompt_password("Passphrase: ")?);
+
+    let is_new = if identities::exists() {
+        !identities::is_encrypted()
+    } else {
+        create
+    };
+
+    if is_new {
+        let confirmation = Zeroizing::new(rpassword::prompt_password("Repeat passphrase: ")?);
+
+        if passphrase != confirmation {
+            anyhow::bail!("passphrases don't match");
+        }
+    }
+
+    Ok(passphrase)
+}
+
+/// Parse base64 secret key or find it among the stored identities by their
+/// index, title or shortname.
+fn find_secret_key(query: &str) -> anyhow::Result<SecretKey> {
+    if let Some(secret_key) = SecretKey::from_base64(query) {
+        return Ok(secret_key);
+    }
+
+    if !identities::exists() {
+        anyhow::bail!("identity not found: {query}");
+    }
+
+    let passphrase = read_passphrase(false)?;
+
+    let list = identities::read(&passphrase)
+        .context("failed to read identities")?;
+
+    let index = identities::find(&list, query)
+        .ok_or_else(|| anyhow::anyhow!("identity not found: {query}"))?;
+
+    Ok(list[index].secret_key().clone())
+}
+
+#[derive(Subcommand)]
+enum IdentityCommand {
+    /// Create new identity with random secret key.
+    Create {
+        /// Title of the identity.
+        #[arg(short, long)]
+        title: String
+    },
+
+    /// List stored identities.
+    List,
+
+    /// Change title of the identity.
+    Rename {
+        /// Index, title or shortname of the identity.
+        identity: String,
+
+        /// New title of the identity.
+        title: String
+    },
+
+    /// Remove identity.
+    Remove {
+        /// Index, title or shortname of the identity.
+        identity: String
+    },
+
+    /// Print secret key of the identity.
+    Export {
+        /// Index, title or shortname of the identity.
+        identity: String,
+
+        /// Print secret key as a BIP39 mnemonic phrase.
+        #[arg(short, long)]
+        mnemonic: bool
+    },
+
+    /// Print safety number of the identity and another user. It's the same for
+    /// both users and can be compared in person to verify their public keys.
+    Safety {
+        /// Index, title or shortname of the identity.
+        identity: String,
+
+        /// Public key of another user.
+        public_key: String
+    },
+
+    /// Import identity from its secret key.
+    ///
+    /// If `secret_key` argument is not specified then stdin value will be used
+    /// as input.
+    Import {
+        /// Title of the identity.
+        #[arg(short, long)]
+        title: String,
+
+        #[arg(short = 'k', long)]
+        secret_key: Option<String>,
+
+        /// Restore secret key from a BIP39 mnemonic phrase.
+        #[arg(short, long)]
+        mnemonic: bool
+    }
+}
+
+impl IdentityCommand {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let create = matches!(self, Self::Create { .. } | Self::Import { .. });
+
+        let passphrase = read_passphrase(create)?;
+
+        let mut list = identities::read(&passphrase)
+            .context("failed to read identities")?;
+
+        let find = |list: &[Identity], identity: &str| {
+            identities::find(list, identity)
+                .ok_or_else(|| anyhow::anyhow!("identity not found: {identity}"))
+        };
+
+        match self {
+            Self::Create { title } => {
+                let identity = Identity::new(title, SecretKey::random(&mut utils::get_rng()));
+
+                println!("Identity created!");
+                println!("  Title:       {}", identity.title());
+                println!("  Shortname:   {} {}", identity.emoji(), identity.shortname());
+                println!("  Public key:  {}", identity.public_key().to_base64());
+                println!("  Fingerprint: {}", identity.fingerprint());
+
+                list.push(identity);
+
+                identities::write(list, &passphrase)?;
+            }
+
+            Self::List => {
+                let rows = list.iter()
+                    .enumerate()
+                    .map(|(i, identity)| [
+                        (i + 1).to_string(),
+                        identity.title().clone(),
+                        format!("{} {}", identity.emoji(), identity.shortname()),
+                        identity.public_key().to_base64(),
+                        identity.created_at().date().to_string()
+                    ])
+                    .collect::<Vec<_>>();
+
+                if !rows.is_empty() {
+                    println!("{}", utils::make_table(
+                        ["#", "Title", "Shortname", "Public key", "Created at"],
+                        rows
+                    ));
+                }
+            }
+
+            Self::Rename { identity, title } => {
+                let index = find(&list, &identity)?;
+
+                list[index].set_title(title);
+
+                identities::write(list, &passphrase)?;
+            }
+
+            Self::Remove { identity } => {
+                let index = find(&list, &identity)?;
+
+                list.remove(index);
+
+                identities::write(list, &passphrase)?;
+            }
+
+            Self::Export { identity, mnemonic } => {
+                let index = find(&list, &identity)?;
+
+                let secret_key = if mnemonic {
+                    Zeroizing::new(identities::secret_key_to_mnemonic(list[index].secret_key())?)
+                } else {
+                    Zeroizing::new(list[index].secret_key().to_base64())
+                };
+
+                let mut stdout = std::io::stdout();
+
+                stdout.write_all(secret_key.as_bytes())?;
+                stdout.flush()?;
+            }
+
+            Self::Safety { identity, public_key } => {
+                let index = find(&list, &identity)?;
+
+                let public_key = PublicKey::from_base64(public_key)
+                    .ok_or_else(|| anyhow::anyhow!("invalid public key format"))?;
+
+                let safety_number = utils::safety_number(
+                    list[index].public_key().to_bytes(),
+                    public_key.to_bytes()
+                );
+
+                println!("Safety number:");
+
+                for line in safety_number.split(' ').collect::<Vec<_>>().chunks(4) {
+                    println!("  {}", line.join(" "));
+                }
+            }
+
+            Self::Import { title, secret_key, mnemonic } => {
+                let secret_key = arg_or_stdin(secret_key)?;
+
+                let secret_key = if mnemonic {
+                    identities::secret_key_from_mnemonic(&secret_key)?
+                } else {
+                    SecretKey::from_base64(secret_key.trim())
+                        .ok_or_else(|| anyhow::anyhow!("failed to decode secret key"))?
+                };
+
+                if list.iter().any(|identity| identity.secret_key() == &secret_key) {
+                    anyhow::bail!("identity with this secret key already exists");
+                }
+
+                list.push(Identity::new(title, secret_key));
+
+                identities::write(list, &passphrase)?;
+            }
         }
 
         Ok(())
@@ -169,15 +562,83 @@ enum SpaceCommand {
         /// Shard node address which will be added to the space sharing link.
         #[arg(short, long = "shard")]
         shards: Vec<String>,
+
+        /// Title of the space which will be added to the space sharing link.
+        #[arg(short, long)]
+        title: Option<String>,
+
+        /// Description of the space which will be added to the space sharing
+        /// link.
+        #[arg(short, long)]
+        description: Option<String>,
+
+        /// Print QR code of the space sharing link.
+        #[arg(long)]
+        qr: bool
     },
 
     /// Import space to the database.
     Import {
-        /// Space sharing link.
+        /// Space sharing link or `flowerchat://space/` URI.
         #[arg(short, long)]
         link: String
     },
 
+    /// List stored spaces.
+    List,
+
+    /// Show information about the stored space.
+    Info {
+        /// Space ID or root block hash.
+        space: String
+    },
+
+    /// Change title of the stored space.
+    Rename {
+        /// Space ID or root block hash.
+        space: String,
+
+        /// New title of the space.
+        title: String
+    },
+
+    /// Remove the space with all its users, rooms and messages from the
+    /// database. Blockchain storage of the space is not affected.
+    Remove {
+        /// Space ID or root block hash.
+        space: String
+    },
+
+    /// Manage shards of the stored space.
+    Shards {
+        #[command(subcommand)]
+        command: ShardsCommand
+    },
+
+    /// Make sharing link of the stored space with its healthiest shards.
+    Share {
+        /// Space ID or root block hash.
+        space: String,
+
+        /// Maximal amount of shards added to the link.
+        #[arg(short = 'n', long, default_value_t = shards::SHARE_LINK_SHARDS)]
+        max_shards: usize,
+
+        /// Add only shards with public addresses.
+        #[arg(long)]
+        public: bool,
+
+        /// Secret key or identity (index, title or shortname) of the space
+        /// creator. If set then the link will be signed and will contain
+        /// title of the space.
+        #[arg(short = 'k', long)]
+        secret_key: Option<String>,
+
+        /// Print QR code of the space sharing link.
+        #[arg(long)]
+        qr: bool
+    },
+
     /// Serve space to other nodes (start blockchain shard).
     Serve {
         /// Path to the blockchain database file.
@@ -188,6 +649,11 @@ enum SpaceCommand {
         #[arg(short, long = "shard")]
         shards: Vec<String>,
 
+        /// Space sharing link or `flowerchat://space/` URI. If the blockchain
+        /// is not stored yet, it will be downloaded from the link's shards.
+        #[arg(long)]
+        link: Option<String>,
+
         /// Local address of the shard.
         #[arg(
             short, long,
@@ -210,7 +676,25 @@ enum SpaceCommand {
 
         /// Maximal amount of inactive shards.
         #[arg(long, default_value_t = 1024)]
-        max_inactive_shards: usize
+        max_inactive_shards: usize,
+
+        /// Print QR code of the space sharing link.
+        #[arg(long)]
+        qr: bool
+    },
+
+    /// Remove locally indexed users, rooms and messages of the space and index
+    /// them again from its blockchain.
+    Reindex {
+        /// Space ID or root block hash.
+        space: String
+    },
+
+    /// Compare locally indexed users, rooms and messages of the space with
+    /// its blockchain and report discrepancies.
+    Verify {
+        /// Space ID or root block hash.
+        space: String
     },
 
     /// Run validator to accept pending transactions and create new blocks.
@@ -233,11 +717,234 @@ enum SpaceCommand {
     }
 }
 
+#[derive(Subcommand)]
+enum ShardsCommand {
+    /// List shards of the space.
+    List {
+        /// Space ID or root block hash.
+        space: String
+    },
+
+    /// Add shard addresses to the space.
+    Add {
+        /// Space ID or root block hash.
+        space: String,
+
+        /// Shard node address.
+        #[arg(required = true)]
+        addresses: Vec<String>
+    },
+
+    /// Remove shard address from the space.
+    Remove {
+        /// Space ID or root block hash.
+        space: String,
+
+        /// Shard node address.
+        address: String
+    },
+
+    /// Check availability of the space shards and root blocks they serve.
+    Probe {
+        /// Space ID or root block hash.
+        space: String
+    }
+}
+
+impl ShardsCommand {
+    pub async fn run(self, database: Database) -> anyhow::Result<()> {
+        match self {
+            Self::List { space } => {
+                let shards = find_space(&database, &space)?
+                    .shards_info()
+                    .context("failed to get shards list for the space")?;
+
+                let rows = shards.into_iter()
+                    .map(|shard| [
+                        shard.address,
+                        shard.last_seen
+                            .map(format_timestamp)
+                            .unwrap_or_else(|| String::from("never")),
+                        shard.failures.to_string()
+                    ])
+                    .collect::<Vec<_>>();
+
+                if !rows.is_empty() {
+                    println!("{}", utils::make_table(
+                        ["Address", "Last seen", "Failures"],
+                        rows
+                    ));
+                }
+            }
+
+            Self::Add { space, addresses } => {
+                let space = find_space(&database, &space)?;
+
+                for address in addresses {
+                    space.add_shard(address)
+                        .context("failed to add shard address to the space")?;
+                }
+            }
+
+            Self::Remove { space, address } => {
+                let removed = find_space(&database, &space)?
+                    .remove_shard(&address)
+                    .context("failed to remove shard address from the space")?;
+
+                if !removed {
+                    anyhow::bail!("space has no such shard: {address}");
+                }
+            }
+
+            Self::Probe { space } => {
+                let space = find_space(&database, &space)?;
+
+                let root_block = space.root_block()
+                    .context("failed to get space root block")?;
+
+                let shards = space.shards()
+                    .context("failed to get shards list for the space")?;
+
+                let client = Client::default();
+
+                let probes = shards.into_iter().map(|address| {
+                    let client = &client;
+
+                    async move {
+                        let result = shards::probe_root_block(client, &address).await;
+
+                        (address, result)
+                    }
+                });
+
+                let mut rows = Vec::new();
+
+                for (address, result) in futures::future::join_all(probes).await {
+                    let is_reachable = result.is_some_and(|(served_root_block, _)| {
+                        served_root_block == root_block
+                    });
+
+                    space.record_shard_probe(&address, is_reachable)
+                        .context("failed to save shard probe result")?;
+
+                    let row = match result {
+                        Some((served_root_block, latency)) => [
+                            address,
+                            if served_root_block == root_block {
+                                String::from("reachable")
+                            } else {
+                                String::from("wrong space")
+                            },
+                            format!("{} ms", latency.as_millis()),
+                            served_root_block.to_base64()
+                        ],
+
+                        None => [
+                            address,
+                            String::from("unreachable"),
+                            String::from("-"),
+                            String::from("-")
+                        ]
+                    };
+
+                    rows.push(row);
+                }
+
+                if !rows.is_empty() {
+                    println!("{}", utils::make_table(
+                        ["Address", "Status", "Latency", "Root block"],
+                        rows
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Format timestamp for the command line output.
+fn format_timestamp(timestamp: time::UtcDateTime) -> String {
+    format!(
+        "{} {:02}:{:02}:{:02} UTC",
+        timestamp.date(),
+        timestamp.hour(),
+        timestamp.minute(),
+        timestamp.second()
+    )
+}
+
+/// Print QR code of the space sharing link URI.
+fn print_qr_code(link: &ShareLink) -> anyhow::Result<()> {
+    let code = utils::qr_code(link.to_uri()?)
+        .context("failed to render share link QR code")?;
+
+    println!();
+    println!("{code}");
+
+    Ok(())
+}
+
+/// Find stored space by its ID or root block hash.
+fn find_space(database: &Database, space: &str) -> anyhow::Result<SpaceRecord> {
+    if let Ok(space_id) = space.parse::<i64>() {
+        return SpaceRecord::open(database.clone(), space_id)
+            .context("failed to open space record");
+    }
+
+    let root_block = Hash::from_base64(space)
+        .ok_or_else(|| anyhow::anyhow!("invalid space root block hash format"))?;
+
+    SpaceRecord::find(database.clone(), &root_block)
+        .context("failed to find space record")?
+        .ok_or_else(|| anyhow::anyhow!("there's no space record with such root block hash"))
+}
+
+/// Download blockchain of the space from its shards and decode all of its
+/// transactions into flowerchat events.
+async fn download_events(space: &SpaceRecord) -> anyhow::Result<Vec<HandlerEvent>> {
+    let mut stdout = std::io::stdout();
+
+    let client = Client::default();
+    let mut pool = ShardsPool::new(space.shards()?);
+
+    stdout.write_all(b"Bootstrapping shards pool...")?;
+    stdout.flush()?;
+
+    pool.update(&client).await;
+
+    stdout.write_all(format!(
+        " {} active, {} inactive\n",
+        pool.active().count(),
+        pool.inactive().count()
+    ).as_bytes())?;
+
+    stdout.write_all(b"Downloading blockchain...")?;
+    stdout.flush()?;
+
+    let viewer = Viewer::open(
+        client,
+        pool.active(),
+        Some(space.root_block()?)
+    ).await.context("failed to open blockchain viewer")?;
+
+    let Some(viewer) = viewer else {
+        anyhow::bail!("none of shards provides space blockchain");
+    };
+
+    let events = client::read_chain(viewer).await?;
+
+    stdout.write_all(format!(" {} transactions\n", events.len()).as_bytes())?;
+    stdout.flush()?;
+
+    Ok(events)
+}
+
 impl SpaceCommand {
     #[inline]
     pub async fn run(self, database: Database) -> anyhow::Result<()> {
         match self {
-            Self::Create { path, secret_key, shards } => {
+            Self::Create { path, secret_key, shards, title, description, qr } => {
                 let mut rng = utils::get_rng();
 
                 let secret_key = match secret_key {
@@ -269,93 +976,206 @@ impl SpaceCommand {
                 storage.write_block(&block)
                     .context("failed to write root block to the blockchain")?;
 
-                let share_link = ShareLink::new::<String>(
+                let mut share_link = ShareLink::new::<String>(
                     block_hash,
                     secret_key.public_key(),
                     shards
-                );
+                ).with_created_at(time::UtcDateTime::now().unix_timestamp() as u64);
+
+                if let Some(title) = title {
+                    share_link = share_link.with_title(title);
+                }
+
+                if let Some(description) = description {
+                    share_link = share_link.with_description(description);
+                }
+
+                let share_link = share_link.sign(&secret_key)
+                    .context("failed to sign share link")?;
 
                 println!("Space created!");
                 println!("  Root block: {}", block_hash.to_base64());
                 println!("  Public key: {}", secret_key.public_key().to_base64());
                 println!("  Secret key: {}", secret_key.to_base64());
-                println!("  Share link: {}", share_link.to_base64()?);
+                println!("  Share link: {}", share_link.to_uri()?);
+
+                if qr {
+                    print_qr_code(&share_link)?;
+                }
             }
 
             Self::Import { link } => {
-                let link = ShareLink::from_base64(link)
+                let link = ShareLink::parse(link)
                     .context("invalid share link format")?;
 
-                let space = SpaceRecord::find(database.clone(), link.root_block())
-                    .context("failed to find space in the database")?;
+                SpaceRecord::import(database, &link)
+                    .context("failed to import space")?;
 
-                let space = match space {
-                    Some(space) => space,
-                    None => {
-                        SpaceRecord::create(database.clone(), &SpaceInfo {
-                            title: String::new(),
-                            root_block: *link.root_block(),
-                            author: link.public_key().to_owned()
-                        }).context("failed to create space")?
-                    }
-                };
+                println!("Space imported");
 
-                for address in link.shards() {
-                    space.add_shard(address)
-                        .context("failed to add shard address to the space")?;
+                if let Some(title) = link.title() {
+                    println!("  Title: {title}");
                 }
 
+                if let Some(description) = link.description() {
+                    println!("  Description: {description}");
+                }
+
+                if !link.is_signed() {
+                    println!("  Warning: share link is not signed by the space author");
+                }
+
+                println!("  Root block: {}", link.root_block().to_base64());
+                println!("  Public key: {}", link.public_key().to_base64());
+                println!("  Share link: {}", link.to_uri()?);
+            }
+
+            Self::List => {
+                let rows = database.spaces()
+                    .map(|(space, info)| [
+                        space.id().to_string(),
+                        info.title.clone(),
+                        format!("{} {}", info.emoji(), info.shortname()),
+                        info.root_block.to_base64(),
+                        info.author.to_base64()
+                    ])
+                    .collect::<Vec<_>>();
+
+                if !rows.is_empty() {
+                    println!("{}", utils::make_table(
+                        ["#", "Title", "Shortname", "Root block", "Public key"],
+                        rows
+                    ));
+                }
+            }
+
+            Self::Info { space } => {
+                let space = find_space(&database, &space)?;
+
+                let info = space.load()
+                    .context("failed to read space info")?;
+
                 let shards = space.shards()
                     .context("failed to get shards list for the space")?;
 
-                let share_link = ShareLink::new(
-                    space.root_block()?,
-                    space.author()?,
+                let stats = space.stats()
+                    .context("failed to count space entries")?;
+
+                let synced_at = space.synced_at()
+                    .context("failed to get space synchronization time")?
+                    .map(format_timestamp)
+                    .unwrap_or_else(|| String::from("never"));
+
+                println!("Space #{}", space.id());
+                println!("  Title: {}", info.title);
+                println!("  Shortname: {} {}", info.emoji(), info.shortname());
+                println!("  Root block: {}", info.root_block.to_base64());
+                println!("  Author: {}", info.author.to_base64());
+                println!("  Rooms: {}", stats.rooms);
+                println!("  Users: {}", stats.users);
+                println!("  Messages: {}", stats.messages);
+                println!("  Last sync: {synced_at}");
+                println!("  Shards: {}", shards.len());
+
+                for address in shards {
+                    println!("    {address}");
+                }
+            }
+
+            Self::Rename { space, title } => {
+                find_space(&database, &space)?
+                    .update_title(title)
+                    .context("failed to update space title")?;
+            }
+
+            Self::Remove { space } => {
+                let space = find_space(&database, &space)?;
+
+                let id = space.id();
+
+                space.remove()
+                    .context("failed to remove space")?;
+
+                println!("Space #{id} removed");
+            }
+
+            Self::Shards { command } => command.run(database).await?,
+
+            Self::Share { space, max_shards, public, secret_key, qr } => {
+                let space = find_space(&database, &space)?;
+
+                let info = space.load()
+                    .context("failed to read space info")?;
+
+                let shards = space.shards()
+                    .context("failed to get shards list for the space")?;
+
+                println!("Probing {} shards...", shards.len());
+
+                let scores = shards::probe_all(
+                    &Client::default(),
+                    info.root_block,
+                    shards.clone()
+                ).await;
+
+                for address in &shards {
+                    if let Some(score) = scores.get(address) {
+                        space.record_shard_probe(address, score.failures == 0)
+                            .context("failed to save shard probe result")?;
+                    }
+                }
+
+                let shards = shards::share_link_shards(
+                    &scores,
+                    shards,
+                    max_shards,
+                    public
+                );
+
+                let mut share_link = ShareLink::new(
+                    info.root_block,
+                    info.author,
                     shards
                 );
 
-                println!("Space imported");
+                if let Some(secret_key) = secret_key {
+                    let secret_key = find_secret_key(&secret_key)?;
+
+                    share_link = share_link.with_title(&info.title)
+                        .with_created_at(time::UtcDateTime::now().unix_timestamp() as u64)
+                        .sign(&secret_key)
+                        .context("failed to sign share link")?;
+                }
+
                 println!("  Root block: {}", share_link.root_block().to_base64());
                 println!("  Public key: {}", share_link.public_key().to_base64());
-                println!("  Share link: {}", share_link.to_base64()?);
+                println!("  Shards: {}", share_link.shards().len());
+                println!("  Share link: {}", share_link.to_uri()?);
+
+                if qr {
+                    print_qr_code(&share_link)?;
+                }
             }
 
             Self::Serve {
                 path,
                 shards,
+                link,
                 local_address,
                 remote_address,
                 max_active_shards,
-                max_inactive_shards
+                max_inactive_shards,
+                qr
             } => {
                 let mut stdout = std::io::stdout();
 
+                let link = link.map(ShareLink::parse)
+                    .transpose()
+                    .context("invalid share link format")?;
+
                 let storage = SqliteStorage::open(path)
                     .context("failed to open blockchain storage")?;
 
-                // TODO: allow serving empty blockchain
-
-                let root_block = storage.root_block()
-                    .context("failed to get root block from the storage")?;
-
-                let Some(root_block) = root_block else {
-                    anyhow::bail!("root block is not stored");
-                };
-
-                let root_block = storage.read_block(&root_block)
-                    .context("failed to read root block from the storage")?;
-
-                let Some(root_block) = root_block else {
-                    anyhow::bail!("root block is not stored");
-                };
-
-                let (is_valid, root_block_hash, public_key) = root_block.verify()
-                    .context("failed to verify root block of the blockchain")?;
-
-                if !is_valid {
-                    anyhow::bail!("root block is invalid");
-                }
-
                 let client = Client::default();
                 let mut pool = ShardsPool::default();
 
@@ -363,6 +1183,10 @@ impl SpaceCommand {
                     .with_max_inactive(max_inactive_shards)
                     .add_shards(shards);
 
+                if let Some(link) = &link {
+                    pool.add_shards(link.shards());
+                }
+
                 stdout.write_all(b"Bootstrapping shards pool...")?;
                 stdout.flush()?;
 
@@ -376,15 +1200,78 @@ impl SpaceCommand {
 
                 stdout.flush()?;
 
-                // TODO
-                // if let Some(remote_address) = &remote_address {
-                //     stdout.write_all(b"Sharing remote address to network shards...")?;
-                //     stdout.flush()?;
+                let root_block = storage.root_block()
+                    .context("failed to get root block from the storage")?;
+
+                let root_block = match (root_block, &link) {
+                    (Some(root_block), _) => root_block,
+
+                    // Download the blockchain from the shards of the share
+                    // link if it's not stored yet.
+                    (None, Some(link)) => {
+                        stdout.write_all(b"Downloading blockchain...")?;
+                        stdout.flush()?;
+
+                        let viewer = Viewer::open(
+                            client.clone(),
+                            pool.active(),
+                            Some(*link.root_block())
+                        ).await.context("failed to open blockchain viewer")?;
+
+                        let Some(mut viewer) = viewer else {
+                            anyhow::bail!("none of shards provides space blockchain");
+                        };
+
+                        let mut blocks = 0;
+
+                        // Viewer verifies blocks before returning them and
+                        // returns `None` when the end of the known blockchain
+                        // is reached.
+                        while let Some(block) = viewer.forward().await {
+                            // Don't store blockchain of another space if the
+                            // link's public key doesn't match its creator.
+                            if blocks == 0 &&
+                                (&block.hash != link.root_block() || &block.public_key != link.public_key())
+                            {
+                                anyhow::bail!("downloaded blockchain doesn't match the share link");
+                            }
+
+                            storage.write_block(&block.block)
+                                .context("failed to write block to the storage")?;
+
+                            blocks += 1;
+                        }
+
+                        stdout.write_all(format!(" {blocks} blocks\n").as_bytes())?;
+                        stdout.flush()?;
+
+                        *link.root_block()
+                    }
+
+                    (None, None) => {
+                        anyhow::bail!("root block is not stored, use --link to download the blockchain");
+                    }
+                };
+
+                let root_block = storage.read_block(&root_block)
+                    .context("failed to read root block from the storage")?;
+
+                let Some(root_block) = root_block else {
+                    anyhow::bail!("root block is not stored");
+                };
 
-                //     for address in pool.active() {
-                //         todo!();
-                //     }
-                // }
+                let (is_valid, root_block_hash, public_key) = root_block.verify()
+                    .context("failed to verify root block of the blockchain")?;
+
+                if !is_valid {
+                    anyhow::bail!("root block is invalid");
+                }
+
+                if let Some(link) = &link &&
+                    (link.root_block() != &root_block_hash || link.public_key() != &public_key)
+                {
+                    anyhow::bail!("stored blockchain doesn't match the share link");
+                }
 
                 let share_link = ShareLink::new(
                     root_block_hash,
@@ -408,22 +1295,126 @@ impl SpaceCommand {
 
                 stdout.write_all(format!(
                     "  Share link: {}\n",
-                    share_link.to_base64()?
+                    share_link.to_uri()?
                 ).as_bytes())?;
 
+                if qr {
+                    print_qr_code(&share_link)?;
+                }
+
                 stdout.flush()?;
 
-                serve_shard(Shard {
-                    client,
-                    shards: pool,
+                let shard = tokio::spawn(serve_shard(Shard {
+                    client: client.clone(),
+                    shards: pool.clone(),
                     local_address,
-                    remote_address,
+                    remote_address: remote_address.clone(),
                     storage,
                     security_rules: SecurityRules {
                         ..SecurityRules::default()
                     },
                     settings: ShardSettings::default()
-                }, Handle::current()).await?;
+                }, Handle::current()));
+
+                if let Some(remote_address) = remote_address {
+                    stdout.write_all(b"Checking remote address reachability...")?;
+                    stdout.flush()?;
+
+                    // Do not publish address which other shards can't
+                    // connect to.
+                    if shards::check_loopback(&client, root_block_hash, &remote_address).await {
+                        stdout.write_all(b" Done\n")?;
+                        stdout.flush()?;
+
+                        // Remember shards which accepted our address if the
+                        // served space is stored locally.
+                        let space = SpaceRecord::find(database.clone(), &root_block_hash)
+                            .context("failed to find space record")?;
+
+                        tokio::spawn(shards::announce_remote_address(
+                            client,
+                            pool,
+                            remote_address,
+                            space,
+                            move |address, result| {
+                                let mut stdout = std::io::stdout();
+
+                                let message = match result {
+                                    Ok(_) => format!("Remote address is shared with {address}\n"),
+                                    Err(err) => format!("Failed to share remote address with {address}: {err}\n")
+                                };
+
+                                let _ = stdout.write_all(message.as_bytes())
+                                    .and_then(|_| stdout.flush());
+                            }
+                        ));
+                    }
+
+                    else {
+                        stdout.write_all(b" Error\n")?;
+                        stdout.write_all(b"Remote address is unreachable and will not be shared\n")?;
+                        stdout.flush()?;
+                    }
+                }
+
+                shard.await
+                    .context("shard task failed")??;
+            }
+
+            Self::Reindex { space } => {
+                let space = find_space(&database, &space)?;
+
+                let events = download_events(&space).await?;
+
+                let space = database.spawn(move |database| {
+                    database.transaction(|| {
+                        space.clear_index()
+                            .context("failed to clear space index")?;
+
+                        client::index_events(&database, &space, events)
+                    })?;
+
+                    Ok(space)
+                }).await?;
+
+                let index = SpaceIndex::read(&space)
+                    .context("failed to read space index")?;
+
+                println!("Space reindexed: {} entries", index.len());
+            }
+
+            Self::Verify { space } => {
+                let space = find_space(&database, &space)?;
+                let info = space.load()?;
+
+                let events = download_events(&space).await?;
+
+                // Replay the blockchain into a temporary database to compare
+                // it with the local one.
+                let expected = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
+                    let database = Database::open_in_memory()?;
+
+                    let space = SpaceRecord::create(database.clone(), &info)?;
+
+                    client::index_events(&database, &space, events)?;
+
+                    Ok(SpaceIndex::read(&space)?)
+                }).await??;
+
+                let local = SpaceIndex::read(&space)
+                    .context("failed to read space index")?;
+
+                let discrepancies = local.compare(&expected);
+
+                for discrepancy in &discrepancies {
+                    println!("  {discrepancy}");
+                }
+
+                if !discrepancies.is_empty() {
+                    anyhow::bail!("found {} discrepancies, use `space reindex` to fix them", discrepancies.len());
+                }
+
+                println!("Space index is valid: {} entries", local.len());
             }
 
             Self::Validate {
@@ -479,8 +1470,19 @@ impl SpaceCommand {
     }
 }
 
-#[tokio::main(flavor = "multi_thread")]
-async fn main() -> anyhow::Result<()> {
+fn main() -> anyhow::Result<()> {
+    // On Unix the local offset can't be determined once the process has
+    // more than one thread, so it's taken before the runtime is started.
+    let local_offset = time::UtcOffset::current_local_offset().ok();
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .context("failed to start async runtime")?
+        .block_on(run(local_offset))
+}
+
+async fn run(local_offset: Option<time::UtcOffset>) -> anyhow::Result<()> {
     std::fs::create_dir_all(consts::DATA_FOLDER.as_path())
         .map_err(|err| {
             anyhow::anyhow!(err)
@@ -508,6 +1510,8 @@ async fn main() -> anyhow::Result<()> {
             let result = tui::run_app(
                 Handle::current(),
                 database,
+                cli.identities_timeout.map(Duration::from_secs),
+                local_offset,
                 &mut terminal
             ).await;
 
@@ -0,0 +1,296 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// flowerchat
+// Copyright (C) 2025  Nikita Podvirnyi <krypt0nn@vk.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::time::Duration;
+
+use anyhow::Context;
+use time::UtcDateTime;
+
+use libflowerpot::crypto::*;
+use libflowerpot::client::Client;
+use libflowerpot::transaction::Transaction;
+
+use crate::database::Database;
+use crate::database::outbox::{OutboxRecord, OutboxInfo, OutboxStatus};
+
+/// Delay before the first re-announcement of a transaction. Every next attempt
+/// doubles it until `RETRY_MAX_DELAY` is reached.
+pub const RETRY_BASE_DELAY: Duration = Duration::from_secs(15);
+
+/// Maximal delay between two announcements of the same transaction.
+pub const RETRY_MAX_DELAY: Duration = Duration::from_secs(600);
+
+/// Amount of announcement attempts after which not included transaction is
+/// marked as failed.
+pub const MAX_ATTEMPTS: u32 = 12;
+
+/// How often the queue looks for transactions to announce.
+pub const QUEUE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Get delay before the next announcement of a transaction which was already
+/// announced `attempts` times.
+pub fn retry_delay(attempts: u32) -> Duration {
+    RETRY_BASE_DELAY.saturating_mul(1 << attempts.min(16))
+        .min(RETRY_MAX_DELAY)
+}
+
+pub enum Update {
+    /// Transaction was announced to the network shards.
+    Announced {
+        /// Hash of the announced transaction.
+        transaction_hash: Hash,
+
+        /// Total amount of announcement attempts.
+        attempts: u32,
+
+        /// Error returned by the shards, if any.
+        error: Option<String>
+    },
+
+    /// Transaction was not included into the blockchain after all the
+    /// announcement attempts.
+    Failed {
+        /// Hash of the failed transaction.
+        transaction_hash: Hash
+    },
+
+    /// Failed to process outgoing transactions. They're processed again on
+    /// the next queue poll.
+    Error(String)
+}
+
+/// Store signed transaction in the outbox of the space. It will be announced
+/// by the queue after `RETRY_BASE_DELAY` unless announced manually earlier.
+/// Messages with `room_name` are shown in the room until they're included.
+pub fn enqueue(
+    database: Database,
+    space_id: i64,
+    author: PublicKey,
+    room_name: Option<String>,
+    transaction: Transaction
+) -> rusqlite::Result<OutboxRecord> {
+    let now = UtcDateTime::now();
+
+    OutboxRecord::create(database, &OutboxInfo {
+        space_id,
+        transaction,
+        author,
+        room_name,
+        status: OutboxStatus::Queued,
+        attempts: 0,
+        created_at: now,
+        next_attempt_at: now + RETRY_BASE_DELAY
+    })
+}
+
+/// Announce outgoing transaction to the provided shards and schedule its next
+/// announcement.
+pub async fn announce(
+    client: &Client,
+    shards: &[String],
+    record: &mut OutboxRecord
+) -> anyhow::Result<Update> {
+    let database = record.database().clone();
+
+    let (transaction, attempts) = database.spawn({
+        let record = record.clone();
+
+        move |_| {
+            let attempts = record.attempts()
+                .context("failed to get transaction attempts")?;
+
+            Ok((record.transaction()?, attempts + 1))
+        }
+    }).await?;
+
+    let transaction_hash = transaction.hash();
+
+    let error = if shards.is_empty() {
+        Some(String::from("no active shards"))
+    } else {
+        client.put_transaction(shards, &transaction).await
+            .err()
+            .map(|err| err.to_string())
+    };
+
+    let next_attempt_at = UtcDateTime::now() + retry_delay(attempts);
+
+    database.spawn({
+        let mut record = record.clone();
+        let announced = error.is_none();
+
+        move |_| {
+            record.add_attempt(next_attempt_at)
+                .context("failed to schedule next transaction announcement")?;
+
+            if announced {
+                record.mark_announced()
+                    .context("failed to mark transaction as announced")?;
+            }
+
+            Ok(())
+        }
+    }).await?;
+
+    Ok(Update::Announced {
+        transaction_hash,
+        attempts,
+        error
+    })
+}
+
+/// Run outgoing transactions queue of the space. It re-announces pending
+/// transactions until they're included into the blockchain, which is tracked
+/// by the sync loop.
+pub async fn run(
+    database: Database,
+    space_id: i64,
+    client: Client,
+    shards: impl Fn() -> Vec<String>,
+    mut updater: impl FnMut(Update)
+) -> anyhow::Result<()> {
+    loop {
+        // Errors are reported per transaction so they don't stop the queue
+        // of the connection.
+        let records = database.spawn(move |database| {
+            OutboxRecord::due(database, space_id, UtcDateTime::now())
+                .context("failed to query pending outgoing transactions")
+        }).await;
+
+        let records = match records {
+            Ok(records) => records,
+            Err(err) => {
+                updater(Update::Error(format!("{err:#}")));
+
+                Vec::new()
+            }
+        };
+
+        for mut record in records {
+            let failed = database.spawn({
+                let mut record = record.clone();
+
+                move |_| {
+                    let attempts = record.attempts()
+                        .context("failed to get transaction attempts")?;
+
+                    if attempts < MAX_ATTEMPTS {
+                        return Ok(None);
+                    }
+
+                    let transaction_hash = record.transaction_hash()
+                        .context("failed to get transaction hash")?;
+
+                    record.mark_failed()
+                        .context("failed to mark transaction as failed")?;
+
+                    Ok(Some(transaction_hash))
+                }
+            }).await;
+
+            match failed {
+                Ok(Some(transaction_hash)) => updater(Update::Failed { transaction_hash }),
+
+                Ok(None) => match announce(&client, &shards(), &mut record).await {
+                    Ok(update) => updater(update),
+                    Err(err) => updater(Update::Error(format!("failed to announce transaction: {err:#}")))
+                }
+
+                Err(err) => updater(Update::Error(format!("{err:#}")))
+            }
+        }
+
+        tokio::time::sleep(QUEUE_POLL_INTERVAL).await;
+    }
+}
+
+#[test]
+fn test_retry_delay() {
+    assert_eq!(retry_delay(0), RETRY_BASE_DELAY);
+    assert_eq!(retry_delay(1), RETRY_BASE_DELAY * 2);
+    assert_eq!(retry_delay(2), RETRY_BASE_DELAY * 4);
+
+    for attempts in 0..64 {
+        assert!(retry_delay(attempts) <= retry_delay(attempts + 1));
+        assert!(retry_delay(attempts) <= RETRY_MAX_DELAY);
+    }
+
+    assert_eq!(retry_delay(MAX_ATTEMPTS), RETRY_MAX_DELAY);
+    assert_eq!(retry_delay(u32::MAX), RETRY_MAX_DELAY);
+}
+
+#[tokio::test]
+async fn test_max_attempts() -> anyhow::Result<()> {
+    let path = std::env::temp_dir()
+        .join(format!("flowerchat-outbox-test-{}.db", std::process::id()));
+
+    let database = Database::open(&path)?;
+
+    let connection = rusqlite::Connection::open(&path)?;
+
+    // Store raw rows since transactions themselves are not read when they
+    // are out of attempts or not due yet.
+    connection.execute_batch(&format!("
+        INSERT INTO spaces (id, title, root_block, author)
+        VALUES (1, 'test', x'00', x'00');
+
+        INSERT INTO outbox (space_id, transaction_hash, transaction_data, status, attempts, created_at, next_attempt_at)
+        VALUES
+            (1, x'{}', x'00', {announced}, {MAX_ATTEMPTS}, 0, 0),
+            (1, x'{}', x'00', {announced}, {MAX_ATTEMPTS}, 0, {});
+    ",
+        "01".repeat(32),
+        "02".repeat(32),
+        i64::MAX,
+        announced = OutboxStatus::ANNOUNCED
+    ))?;
+
+    let mut failed = Vec::new();
+
+    // The queue never stops so interrupt it after the first iteration.
+    let result = tokio::time::timeout(Duration::from_secs(1), run(
+        database.clone(),
+        1,
+        Client::default(),
+        Vec::new,
+        |update| {
+            if let Update::Failed { transaction_hash } = update {
+                failed.push(transaction_hash);
+            }
+        }
+    )).await;
+
+    assert!(result.is_err());
+    assert_eq!(failed, [Hash::from([1; 32])]);
+
+    // Not due transactions are kept until their next attempt.
+    let statuses = connection.prepare("SELECT status FROM outbox ORDER BY id ASC")?
+        .query_map([], |row| row.get::<_, i64>("status"))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(statuses, [OutboxStatus::FAILED, OutboxStatus::ANNOUNCED]);
+
+    drop(connection);
+    drop(database);
+
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+    }
+
+    Ok(())
+}
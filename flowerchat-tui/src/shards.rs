@@ -0,0 +1,470 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// flowerchat
+// Copyright (C) 2025  Nikita Podvirnyi <krypt0nn@vk.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use time::UtcDateTime;
+
+use libflowerpot::crypto::*;
+use libflowerpot::client::Client;
+use libflowerpot::pool::ShardsPool;
+use libflowerpot::viewer::Viewer;
+
+use crate::database::space::SpaceRecord;
+
+/// How often the shards pool of a connected space is updated.
+pub const POOL_UPDATE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Maximal time a shard can take to respond to the probe request.
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often the remote address of a served shard is announced to the
+/// active shards of the network.
+pub const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Amount of loopback probes made before the remote address is considered
+/// unreachable. The shard may need some time to start accepting connections.
+pub const LOOPBACK_ATTEMPTS: u32 = 5;
+
+/// Amount of failed probes in a row after which the shard is demoted: it's
+/// not used for announcements while there are other working shards.
+pub const MAX_FAILURES: u32 = 3;
+
+/// Default amount of shards added to the space sharing link.
+pub const SHARE_LINK_SHARDS: usize = 16;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ShardScore {
+    /// Response time of the last successful probe.
+    pub latency: Option<Duration>,
+
+    /// Amount of failed probes in a row.
+    pub failures: u32,
+
+    /// Time of the last probe.
+    pub checked_at: Option<UtcDateTime>
+}
+
+impl ShardScore {
+    #[inline]
+    pub const fn is_demoted(&self) -> bool {
+        self.failures >= MAX_FAILURES
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ShardScores(HashMap<String, ShardScore>);
+
+impl ShardScores {
+    #[inline]
+    pub fn get(&self, address: impl AsRef<str>) -> Option<&ShardScore> {
+        self.0.get(address.as_ref())
+    }
+
+    /// Store result of the shard probe.
+    pub fn record(&mut self, address: impl ToString, latency: Option<Duration>) {
+        let score = self.0.entry(address.to_string())
+            .or_default();
+
+        match latency {
+            Some(latency) => {
+                score.latency = Some(latency);
+                score.failures = 0;
+            }
+
+            None => score.failures += 1
+        }
+
+        score.checked_at = Some(UtcDateTime::now());
+    }
+
+    /// Order shards from the fastest to the slowest, excluding demoted ones.
+    /// If all the shards are demoted then they're all returned as is.
+    pub fn rank(&self, shards: impl IntoIterator<Item = String>) -> Vec<String> {
+        let shards = shards.into_iter().collect::<Vec<String>>();
+
+        let mut ranked = shards.iter()
+            .filter(|address| !self.get(address).is_some_and(ShardScore::is_demoted))
+            .cloned()
+            .collect::<Vec<String>>();
+
+        if ranked.is_empty() {
+            return shards;
+        }
+
+        ranked.sort_by_key(|address| {
+            self.get(address)
+                .and_then(|score| score.latency)
+                .unwrap_or(PROBE_TIMEOUT)
+        });
+
+        ranked
+    }
+}
+
+/// Check if shard address is reachable from the internet, so it's useful for
+/// other people. Loopback, private and link-local addresses are not public.
+pub fn is_public_address(address: impl AsRef<str>) -> bool {
+    let address = address.as_ref();
+
+    let host = address.split_once("://")
+        .map(|(_, address)| address)
+        .unwrap_or(address);
+
+    let host = host.split('/').next().unwrap_or_default();
+
+    let ip = host.parse::<SocketAddr>()
+        .map(|address| address.ip())
+        .or_else(|_| host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>());
+
+    match ip {
+        Ok(IpAddr::V4(ip)) => {
+            let [a, b, ..] = ip.octets();
+
+            !(ip.is_private() || ip.is_loopback() || ip.is_link_local() ||
+                ip.is_unspecified() || ip.is_broadcast() || ip.is_documentation() ||
+                (a == 100 && b & 0b1100_0000 == 64))
+        }
+
+        Ok(IpAddr::V6(ip)) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public_address(ip.to_string());
+            }
+
+            !(ip.is_loopback() || ip.is_unspecified() || ip.is_unique_local() ||
+                ip.is_unicast_link_local())
+        }
+
+        Err(_) => {
+            let hostname = host.rsplit_once(':')
+                .map(|(hostname, _)| hostname)
+                .unwrap_or(host)
+                .to_ascii_lowercase();
+
+            !(hostname.is_empty() || hostname == "localhost" ||
+                [".localhost", ".local", ".lan", ".internal", ".home.arpa"]
+                    .iter()
+                    .any(|suffix| hostname.ends_with(suffix)))
+        }
+    }
+}
+
+/// Select shards for the space sharing link: the healthiest ones first,
+/// optionally only with public addresses.
+pub fn share_link_shards(
+    scores: &ShardScores,
+    shards: impl IntoIterator<Item = String>,
+    max_shards: usize,
+    public_only: bool
+) -> Vec<String> {
+    let mut unique = Vec::new();
+
+    for address in shards {
+        if !unique.contains(&address) && (!public_only || is_public_address(&address)) {
+            unique.push(address);
+        }
+    }
+
+    let mut shards = scores.rank(unique);
+
+    shards.truncate(max_shards);
+
+    shards
+}
+
+/// Probe all the shards in parallel and return their scores.
+pub async fn probe_all(
+    client: &Client,
+    root_block: Hash,
+    shards: impl IntoIterator<Item = String>
+) -> ShardScores {
+    let probes = shards.into_iter().map(|address| async move {
+        let latency = probe(client, root_block, &address).await;
+
+        (address, latency)
+    });
+
+    let mut scores = ShardScores::default();
+
+    for (address, latency) in futures::future::join_all(probes).await {
+        scores.record(address, latency);
+    }
+
+    scores
+}
+
+pub enum Update {
+    /// Shards pool was updated.
+    Pool(ShardsPool),
+
+    /// Shard was probed. `latency` is `None` if it's unreachable.
+    Probed {
+        address: String,
+        latency: Option<Duration>
+    },
+
+    /// New working shard was found and saved to the space shards list.
+    Discovered(String),
+
+    /// Failed to save shards info to the database. Shards are still probed.
+    Error(String)
+}
+
+/// Check that the shard provides the blockchain of the space. Return its
+/// response time or `None` if it's unreachable.
+pub async fn probe(
+    client: &Client,
+    root_block: Hash,
+    address: impl ToString
+) -> Option<Duration> {
+    let started_at = Instant::now();
+
+    let viewer = Viewer::open(
+        client.clone(),
+        [address.to_string()],
+        Some(root_block)
+    );
+
+    match tokio::time::timeout(PROBE_TIMEOUT, viewer).await {
+        Ok(Ok(Some(_))) => Some(started_at.elapsed()),
+        _ => None
+    }
+}
+
+/// Open blockchain viewer on the shard without expecting specific root block.
+/// Return hash of the root block served by the shard and its response time,
+/// or `None` if the shard is unreachable.
+pub async fn probe_root_block(
+    client: &Client,
+    address: impl ToString
+) -> Option<(Hash, Duration)> {
+    let started_at = Instant::now();
+
+    let viewer = Viewer::open(
+        client.clone(),
+        [address.to_string()],
+        None
+    );
+
+    match tokio::time::timeout(PROBE_TIMEOUT, viewer).await {
+        Ok(Ok(Some(viewer))) => Some((*viewer.root_block(), started_at.elapsed())),
+        _ => None
+    }
+}
+
+/// Check that the remote address of a served shard is reachable by connecting
+/// back to it and requesting the blockchain with provided root block.
+pub async fn check_loopback(
+    client: &Client,
+    root_block: Hash,
+    remote_address: impl AsRef<str>
+) -> bool {
+    for _ in 0..LOOPBACK_ATTEMPTS {
+        if probe(client, root_block, remote_address.as_ref()).await.is_some() {
+            return true;
+        }
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    false
+}
+
+/// Periodically share remote address of a served shard with the active shards
+/// of the pool. Shards which accepted the address are remembered and, if the
+/// served space is stored locally, saved to its shards list.
+///
+/// `announced` is called with the shard address and the time it accepted our
+/// address for the first time, or the error it returned.
+pub async fn announce_remote_address(
+    client: Client,
+    mut pool: ShardsPool,
+    remote_address: String,
+    space: Option<SpaceRecord>,
+    mut announced: impl FnMut(String, Result<UtcDateTime, String>)
+) {
+    let mut peers = HashMap::<String, UtcDateTime>::new();
+
+    loop {
+        pool.update(&client).await;
+
+        let shards = pool.active()
+            .filter(|address| *address != &remote_address)
+            .cloned()
+            .collect::<Vec<String>>();
+
+        for address in shards {
+            let result = client.put_shard([&address], &remote_address).await;
+
+            let result = match (result, &space) {
+                (Ok(_), Some(space)) => {
+                    let space = space.clone();
+                    let peer = address.clone();
+
+                    space.database().clone().spawn(move |_| {
+                        space.add_shard(&peer)?;
+                        space.record_shard_probe(&peer, true)?;
+
+                        Ok(())
+                    }).await.map_err(|err| format!("failed to save shard: {err}"))
+                }
+
+                (Ok(_), None) => Ok(()),
+                (Err(err), _) => Err(err.to_string())
+            };
+
+            match result {
+                Ok(()) => {
+                    if !peers.contains_key(&address) {
+                        let timestamp = UtcDateTime::now();
+
+                        peers.insert(address.clone(), timestamp);
+
+                        announced(address, Ok(timestamp));
+                    }
+                }
+
+                Err(err) => {
+                    peers.remove(&address);
+
+                    announced(address, Err(err));
+                }
+            }
+        }
+
+        tokio::time::sleep(ANNOUNCE_INTERVAL).await;
+    }
+}
+
+/// Periodically update shards pool of the connected space, probe all its
+/// shards and save newly discovered working shards to the database.
+pub async fn run(
+    space: SpaceRecord,
+    client: Client,
+    pool: impl Fn() -> Option<ShardsPool>,
+    mut updater: impl FnMut(Update)
+) -> anyhow::Result<()> {
+    let database = space.database().clone();
+
+    let root_block = database.spawn({
+        let space = space.clone();
+
+        move |_| space.root_block().context("failed to get space root block")
+    }).await?;
+
+    loop {
+        let Some(mut pool) = pool() else {
+            return Ok(());
+        };
+
+        pool.update(&client).await;
+
+        let active = pool.active()
+            .cloned()
+            .collect::<Vec<String>>();
+
+        let shards = active.iter()
+            .chain(pool.inactive())
+            .cloned()
+            .collect::<Vec<String>>();
+
+        updater(Update::Pool(pool));
+
+        let scores = probe_all(&client, root_block, shards.clone()).await;
+
+        let probes = shards.into_iter()
+            .map(|address| {
+                let latency = scores.get(&address)
+                    .filter(|score| score.failures == 0)
+                    .and_then(|score| score.latency);
+
+                (address, latency)
+            })
+            .collect::<Vec<_>>();
+
+        // Database errors are reported per shard so they don't stop the
+        // shards maintenance of the connection.
+        let result = database.spawn({
+            let space = space.clone();
+            let probes = probes.clone();
+
+            move |_| {
+                let known = space.shards()
+                    .context("failed to get space shards")?;
+
+                let mut discovered = Vec::new();
+                let mut errors = Vec::new();
+
+                for (address, latency) in probes {
+                    let is_discovered = latency.is_some() &&
+                        active.contains(&address) &&
+                        !known.contains(&address);
+
+                    if is_discovered {
+                        match space.add_shard(&address) {
+                            Ok(()) => discovered.push(address.clone()),
+                            Err(err) => errors.push(format!("failed to save discovered shard {address}: {err}"))
+                        }
+                    }
+
+                    if let Err(err) = space.record_shard_probe(&address, latency.is_some()) {
+                        errors.push(format!("failed to save probe result of shard {address}: {err}"));
+                    }
+                }
+
+                Ok((discovered, errors))
+            }
+        }).await;
+
+        for (address, latency) in probes {
+            updater(Update::Probed { address, latency });
+        }
+
+        match result {
+            Ok((discovered, errors)) => {
+                discovered.into_iter().for_each(|address| updater(Update::Discovered(address)));
+                errors.into_iter().for_each(|err| updater(Update::Error(err)));
+            }
+
+            Err(err) => updater(Update::Error(format!("{err:#}")))
+        }
+
+        tokio::time::sleep(POOL_UPDATE_INTERVAL).await;
+    }
+}
+
+#[test]
+fn test_is_public_address() {
+    assert!(is_public_address("1.1.1.1:47901"));
+    assert!(is_public_address("http://example.com:47901"));
+    assert!(is_public_address("[2606:4700::1111]:47901"));
+    assert!(is_public_address("example.com"));
+
+    assert!(!is_public_address("127.0.0.1:47901"));
+    assert!(!is_public_address("192.168.1.2:47901"));
+    assert!(!is_public_address("http://10.0.0.1:47901/"));
+    assert!(!is_public_address("100.64.0.1:47901"));
+    assert!(!is_public_address("[::1]:47901"));
+    assert!(!is_public_address("[fd00::1]:47901"));
+    assert!(!is_public_address("[::ffff:192.168.1.2]:47901"));
+    assert!(!is_public_address("localhost:47901"));
+    assert!(!is_public_address("printer.local"));
+}
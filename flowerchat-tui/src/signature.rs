@@ -0,0 +1,191 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// flowerchat
+// Copyright (C) 2025  Nikita Podvirnyi <krypt0nn@vk.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+
+use libflowerpot::crypto::*;
+
+/// First line of the armored signature.
+const ARMOR_BEGIN: &str = "-----BEGIN FLOWERCHAT SIGNATURE-----";
+
+/// Last line of the armored signature.
+const ARMOR_END: &str = "-----END FLOWERCHAT SIGNATURE-----";
+
+/// Version of the armored signature format.
+const ARMOR_VERSION: &str = "1";
+
+/// Context string of the message hash. It prevents signatures of arbitrary
+/// messages from being reused as signatures of blocks or transactions.
+const HASH_CONTEXT: &str = "flowerchat detached signature v1";
+
+/// Calculate hash of the signed message.
+#[inline]
+fn hash_message(message: impl AsRef<[u8]>) -> Hash {
+    Hash::from(blake3::derive_key(HASH_CONTEXT, message.as_ref()))
+}
+
+/// Signature of an arbitrary message stored separately from it.
+///
+/// Detached signatures are serialized in a text armor format:
+///
+/// ```text
+/// -----BEGIN FLOWERCHAT SIGNATURE-----
+/// Version: 1
+/// Public-Key: <base64 public key of the signer>
+///
+/// <base64 signature>
+/// -----END FLOWERCHAT SIGNATURE-----
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetachedSignature {
+    public_key: PublicKey,
+    signature: Signature
+}
+
+impl DetachedSignature {
+    /// Sign the message with the secret key.
+    pub fn create(
+        secret_key: &SecretKey,
+        message: impl AsRef<[u8]>
+    ) -> anyhow::Result<Self> {
+        let signature = Signature::create(secret_key, hash_message(message))
+            .map_err(|err| anyhow::anyhow!("failed to sign message: {err}"))?;
+
+        Ok(Self {
+            public_key: secret_key.public_key(),
+            signature
+        })
+    }
+
+    /// Public key of the signer.
+    #[inline(always)]
+    pub const fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    /// Verify that the message was signed by the signer's public key.
+    pub fn verify(&self, message: impl AsRef<[u8]>) -> anyhow::Result<bool> {
+        let (is_valid, public_key) = self.signature.verify(hash_message(message))
+            .map_err(|err| anyhow::anyhow!("failed to verify signature: {err}"))?;
+
+        Ok(is_valid && public_key == self.public_key)
+    }
+
+    /// Serialize signature into the text armor format.
+    pub fn to_armor(&self) -> String {
+        format!(
+            "{ARMOR_BEGIN}\nVersion: {ARMOR_VERSION}\nPublic-Key: {}\n\n{}\n{ARMOR_END}\n",
+            self.public_key.to_base64(),
+            BASE64.encode(self.signature.to_bytes())
+        )
+    }
+
+    /// Deserialize signature from the text armor format.
+    pub fn from_armor(armor: impl AsRef<str>) -> anyhow::Result<Self> {
+        let mut lines = armor.as_ref()
+            .lines()
+            .map(str::trim)
+            .skip_while(|line| line.is_empty());
+
+        if lines.next() != Some(ARMOR_BEGIN) {
+            anyhow::bail!("signature armor header is missing");
+        }
+
+        let mut version = None;
+        let mut public_key = None;
+
+        // Read headers until the empty line.
+        for line in lines.by_ref() {
+            if line.is_empty() {
+                break;
+            }
+
+            let Some((name, value)) = line.split_once(':') else {
+                anyhow::bail!("invalid signature armor header: {line}");
+            };
+
+            match name.trim() {
+                "Version" => version = Some(value.trim().to_string()),
+                "Public-Key" => public_key = Some(value.trim().to_string()),
+
+                // Ignore unknown headers for forward compatibility.
+                _ => ()
+            }
+        }
+
+        match version.as_deref() {
+            Some(ARMOR_VERSION) => (),
+            Some(version) => anyhow::bail!("unsupported signature armor version: {version}"),
+            None => anyhow::bail!("signature armor version is missing")
+        }
+
+        let public_key = public_key.as_deref()
+            .and_then(PublicKey::from_base64)
+            .ok_or_else(|| anyhow::anyhow!("signature armor public key is missing or invalid"))?;
+
+        let mut signature = String::new();
+        let mut is_closed = false;
+
+        for line in lines.by_ref() {
+            if line == ARMOR_END {
+                is_closed = true;
+
+                break;
+            }
+
+            signature.push_str(line);
+        }
+
+        if !is_closed {
+            anyhow::bail!("signature armor footer is missing");
+        }
+
+        let signature = BASE64.decode(signature).ok()
+            .and_then(|signature| <[u8; 65]>::try_from(signature).ok())
+            .and_then(|signature| Signature::from_bytes(&signature))
+            .ok_or_else(|| anyhow::anyhow!("invalid signature armor body"))?;
+
+        Ok(Self {
+            public_key,
+            signature
+        })
+    }
+}
+
+#[test]
+fn test_detached_signature() -> anyhow::Result<()> {
+    let secret_key = SecretKey::random(&mut crate::utils::get_rng());
+
+    let signature = DetachedSignature::create(&secret_key, b"hello world")?;
+    let armor = signature.to_armor();
+
+    assert!(armor.starts_with(ARMOR_BEGIN));
+    assert_eq!(DetachedSignature::from_armor(&armor)?, signature);
+
+    assert!(signature.verify(b"hello world")?);
+    assert!(!signature.verify(b"hello world!")?);
+
+    let another_key = SecretKey::random(&mut crate::utils::get_rng()).public_key();
+
+    let forged = armor.replace(&secret_key.public_key().to_base64(), &another_key.to_base64());
+
+    assert!(!DetachedSignature::from_armor(forged)?.verify(b"hello world")?);
+
+    Ok(())
+}
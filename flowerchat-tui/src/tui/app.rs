@@ -17,7 +17,12 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use std::sync::Arc;
+use std::time::Duration;
+
+use time::UtcOffset;
+
 use spin::RwLock;
+use zeroize::Zeroizing;
 
 use tokio::runtime::Handle;
 use tokio::task::JoinHandle;
@@ -29,11 +34,21 @@ use libflowerpot::client::Client;
 use libflowerpot::pool::ShardsPool;
 use libflowerpot::viewer::Viewer;
 
+use flowerchat_protocol::events::Events;
+
 use crate::database::Database;
 use crate::database::space::SpaceRecord;
+use crate::identities::UnlockedIdentities;
+use crate::database::public_room::PublicRoomRecord;
 use crate::client::Update;
+use crate::outbox::Update as OutboxUpdate;
+use crate::shards::{ShardScores, Update as ShardsUpdate};
 
 use crate::tui::terminal_widget::{TerminalWidget, TerminalWidgetCurrentLine};
+use crate::tui::room_view::{RoomView, RoomViewMessage};
+
+/// How often unlocked identities are checked for the timeout.
+const IDENTITIES_TIMEOUT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
 
 // TODO: get rid of actions in favor of shared state.
 
@@ -55,7 +70,49 @@ pub enum Action {
         client: Client,
         shards: ShardsPool,
         viewer: Viewer
-    }
+    },
+
+    /// Close connection to the focused space.
+    Disconnect,
+
+    /// Focus space connection with provided index in the spaces switcher.
+    Focus(usize),
+
+    /// New event was handled by the sync task of the space with provided ID.
+    SpaceEvent {
+        space_id: i64,
+        transaction_hash: Hash,
+
+        /// Name of the public room the event sends a message to, if any.
+        room_name: Option<String>
+    },
+
+    /// Open public room view.
+    OpenRoom(PublicRoomRecord),
+
+    /// Open public room view positioned at the message with provided ID.
+    OpenRoomAt {
+        room: PublicRoomRecord,
+        message_id: i64
+    },
+
+    /// Close currently opened public room view.
+    CloseRoom,
+
+    /// Reload messages of the currently opened public room.
+    RefreshRoom,
+
+    /// Show our outgoing message in the public room with provided name
+    /// before it's included into the blockchain.
+    LocalEcho {
+        space_id: i64,
+        room_name: String,
+        message: RoomViewMessage
+    },
+
+    /// Replace local echo of the message with provided transaction hash by
+    /// its stored version.
+    Reconcile(Hash)
 }
 
 #[derive(Debug)]
@@ -63,25 +120,301 @@ pub struct SpaceConnection {
     pub client: Client,
     pub shards_pool: ShardsPool,
     pub sync_task: JoinHandle<anyhow::Result<()>>,
+    pub outbox_task: JoinHandle<anyhow::Result<()>>,
+    pub shards_task: JoinHandle<anyhow::Result<()>>,
+    pub shard_scores: ShardScores,
     pub space: SpaceRecord,
-    pub identity: SecretKey
+    pub identity: SecretKey,
+
+    /// Name of the space in the spaces switcher.
+    pub title: String,
+
+    /// Amount of new messages received while the space was not focused.
+    pub unread: u64,
+
+    /// Room which was opened when the space lost focus.
+    pub last_room: Option<PublicRoomRecord>
+}
+
+impl Drop for SpaceConnection {
+    fn drop(&mut self) {
+        self.sync_task.abort();
+        self.outbox_task.abort();
+        self.shards_task.abort();
+    }
+}
+
+impl SpaceConnection {
+    /// Active shards of the space ordered by their scores.
+    pub fn announce_shards(&self) -> Vec<String> {
+        self.shard_scores.rank(self.shards_pool.active().cloned())
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct SpaceConnections {
+    connections: Vec<SpaceConnection>,
+    focused: Option<usize>
+}
+
+impl SpaceConnections {
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &SpaceConnection> {
+        self.connections.iter()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.connections.is_empty()
+    }
+
+    /// Index of the focused connection in the spaces switcher.
+    #[inline]
+    pub const fn focused_index(&self) -> Option<usize> {
+        self.focused
+    }
+
+    /// Get focused space connection.
+    pub fn focused(&self) -> Option<&SpaceConnection> {
+        self.connections.get(self.focused?)
+    }
+
+    /// Get connection to the space with provided ID.
+    pub fn get(&self, space_id: i64) -> Option<&SpaceConnection> {
+        self.connections.iter()
+            .find(|connection| connection.space.id() == space_id)
+    }
+
+    /// Get connection to the space with provided ID.
+    pub fn get_mut(&mut self, space_id: i64) -> Option<&mut SpaceConnection> {
+        self.connections.iter_mut()
+            .find(|connection| connection.space.id() == space_id)
+    }
+
+    /// Add new connection and focus it. Previous connection to the same space
+    /// will be closed, keeping its remembered room.
+    pub fn insert(&mut self, mut connection: SpaceConnection) {
+        let index = self.connections.iter()
+            .position(|known| known.space.id() == connection.space.id());
+
+        match index {
+            Some(index) => {
+                if connection.last_room.is_none() {
+                    connection.last_room = self.connections[index].last_room.take();
+                }
+
+                self.connections[index] = connection;
+                self.focused = Some(index);
+            }
+
+            None => {
+                self.connections.push(connection);
+                self.focused = Some(self.connections.len() - 1);
+            }
+        }
+    }
+
+    /// Close focused connection and focus the first remaining one.
+    pub fn remove_focused(&mut self) -> Option<SpaceConnection> {
+        let connection = self.connections.remove(self.focused?);
+
+        self.focused = (!self.connections.is_empty()).then_some(0);
+
+        Some(connection)
+    }
+
+    /// Store room opened in the focused connection to restore it when this
+    /// connection is focused again.
+    pub fn remember_room(&mut self, room: Option<PublicRoomRecord>) {
+        if let Some(focused) = self.focused {
+            self.connections[focused].last_room = room;
+        }
+    }
+
+    /// Count new message received by the space which is not focused. Return
+    /// `false` if the space is focused or not connected.
+    pub fn add_unread(&mut self, space_id: i64) -> bool {
+        let index = self.connections.iter()
+            .position(|connection| connection.space.id() == space_id);
+
+        match index {
+            Some(index) if Some(index) != self.focused => {
+                self.connections[index].unread += 1;
+
+                true
+            }
+
+            _ => false
+        }
+    }
+
+    /// Focus connection with provided index. Return `false` if there's no
+    /// such connection.
+    pub fn focus(&mut self, index: usize) -> bool {
+        if index >= self.connections.len() {
+            return false;
+        }
+
+        self.focused = Some(index);
+        self.connections[index].unread = 0;
+
+        true
+    }
+}
+
+/// Secret value requested from the user with a hidden input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretPrompt {
+    /// Passphrase of the identities file.
+    Passphrase,
+
+    /// Secret key of the imported identity with provided title.
+    SecretKey(String),
+
+    /// Mnemonic phrase of the restored identity with provided title.
+    Mnemonic(String)
+}
+
+impl SecretPrompt {
+    /// Name of the requested value shown before the input.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Passphrase   => "passphrase",
+            Self::SecretKey(_) => "secret key",
+            Self::Mnemonic(_)  => "mnemonic phrase"
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct AppState {
     pub terminal_widget: Arc<RwLock<TerminalWidget>>,
     pub database: Database,
-    pub connection: Arc<RwLock<Option<SpaceConnection>>>
+    pub connections: Arc<RwLock<SpaceConnections>>,
+
+    /// Opened room of the focused space.
+    pub room: Arc<RwLock<Option<RoomView>>>,
+
+    /// Identities unlocked with the user's passphrase.
+    pub identities: Arc<RwLock<UnlockedIdentities>>,
+
+    /// Passphrase for the new identities file waiting to be entered again.
+    pub new_passphrase: Arc<RwLock<Option<Zeroizing<String>>>>,
+
+    /// Secret value the hidden input is currently asked for.
+    pub secret_prompt: Arc<RwLock<Option<SecretPrompt>>>,
+
+    /// Offset of the local timezone, or `None` if it's unknown.
+    pub local_offset: Option<UtcOffset>,
+
+    /// Lock held while the identities are modified so changes made by
+    /// concurrent commands are applied one after another.
+    identities_updates: Arc<parking_lot::Mutex<()>>
 }
 
 impl AppState {
-    pub fn new(database: Database) -> Self {
+    pub fn new(
+        database: Database,
+        identities_timeout: Option<Duration>,
+        local_offset: Option<UtcOffset>
+    ) -> Self {
         Self {
             terminal_widget: Arc::new(RwLock::new(TerminalWidget::default())),
             database,
-            connection: Arc::new(RwLock::new(None))
+            connections: Arc::new(RwLock::new(SpaceConnections::default())),
+            room: Arc::new(RwLock::new(None)),
+            identities: Arc::new(RwLock::new(UnlockedIdentities::new(identities_timeout))),
+            new_passphrase: Arc::new(RwLock::new(None)),
+            secret_prompt: Arc::new(RwLock::new(None)),
+            local_offset,
+            identities_updates: Arc::new(parking_lot::Mutex::new(()))
         }
     }
+
+    /// Get offset of the local timezone, or UTC if it's unknown.
+    #[inline]
+    pub fn offset(&self) -> UtcOffset {
+        self.local_offset.unwrap_or(UtcOffset::UTC)
+    }
+
+    /// Ask user to enter secret value with a hidden input so it's not kept
+    /// in the terminal history. It's handled once the user presses enter.
+    pub fn ask_secret(
+        &self,
+        prompt: SecretPrompt,
+        message: &str,
+        output: impl Fn(Action)
+    ) {
+        output(Action::TerminalPush(String::from(message)));
+
+        let mut terminal_widget = self.terminal_widget.write();
+
+        terminal_widget.prefix = Some(String::from(prompt.name()));
+        terminal_widget.secret_input = true;
+
+        *self.secret_prompt.write() = Some(prompt);
+    }
+
+    /// Modify current identities state. Key derivation is intentionally slow
+    /// so the callback is executed outside of the async runtime.
+    ///
+    /// Modifications are applied one at a time to the latest state, so
+    /// concurrent commands don't overwrite each other's changes.
+    pub async fn modify_identities<T: Send + 'static>(
+        &self,
+        callback: impl FnOnce(&mut UnlockedIdentities) -> anyhow::Result<T> + Send + 'static
+    ) -> anyhow::Result<T> {
+        let identities = self.identities.clone();
+        let updates = self.identities_updates.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let _guard = updates.lock();
+
+            let mut modified = identities.read().clone();
+            let was_unlocked = modified.is_unlocked();
+
+            let result = callback(&mut modified)?;
+
+            let mut identities = identities.write();
+
+            // Don't unlock identities again if they were locked meanwhile.
+            if !was_unlocked || identities.is_unlocked() {
+                *identities = modified;
+            }
+
+            Ok(result)
+        }).await?
+    }
+
+    /// Call provided function with the focused space connection. Return `None`
+    /// if no space is focused.
+    pub fn with_focused<T>(
+        &self,
+        callback: impl FnOnce(&SpaceConnection) -> T
+    ) -> Option<T> {
+        self.connections.read()
+            .focused()
+            .map(callback)
+    }
+}
+
+/// Replace opened room by the last opened room of the focused space.
+async fn restore_room(state: &AppState) -> anyhow::Result<()> {
+    let last_room = state.with_focused(|connection| connection.last_room.clone())
+        .flatten();
+
+    let room = match last_room {
+        Some(room) => {
+            let offset = state.offset();
+
+            Some(state.database.spawn(move |_| RoomView::load(room, offset)).await?)
+        }
+        None => None
+    };
+
+    *state.room.write() = room;
+
+    Ok(())
 }
 
 pub fn run_actions_handler(
@@ -91,10 +424,32 @@ pub fn run_actions_handler(
     let (action_sender, mut action_receiver) = unbounded_channel();
     let (updates_sender, updates_receiver) = unbounded_channel();
 
+    // Forget unlocked identities when their timeout has passed.
+    if let Some(timeout) = state.identities.read().timeout() {
+        let state = state.clone();
+        let updates_sender = updates_sender.clone();
+
+        runtime.spawn(async move {
+            loop {
+                tokio::time::sleep(timeout.min(IDENTITIES_TIMEOUT_CHECK_INTERVAL)).await;
+
+                if state.identities.write().lock_expired() {
+                    state.terminal_widget.write().push("identities are locked, use `unlock` to unlock them again");
+
+                    let _ = updates_sender.send(());
+                }
+            }
+        });
+    }
+
     runtime.clone().spawn({
         let action_sender = action_sender.clone();
 
         async move {
+            // Whether the opened room reload is already queued, so events
+            // handled in a row don't reload it once per event.
+            let mut refresh_queued = false;
+
             while let Some(action) = action_receiver.recv().await {
                 match action {
                     Action::TerminalPush(text) => {
@@ -110,27 +465,29 @@ pub fn run_actions_handler(
                     }
 
                     Action::RequestSpaceRecord(space, sender) => {
-                        let space = match space.parse::<i64>() {
-                            Ok(space_id) => {
-                                SpaceRecord::open(state.database.clone(), space_id)
-                                    .map_err(|err| {
-                                        anyhow::anyhow!(err)
-                                            .context("failed to open space record")
-                                    })
-                            }
+                        let space = state.database.spawn(move |database| {
+                            match space.parse::<i64>() {
+                                Ok(space_id) => {
+                                    SpaceRecord::open(database, space_id)
+                                        .map_err(|err| {
+                                            anyhow::anyhow!(err)
+                                                .context("failed to open space record")
+                                        })
+                                }
 
-                            Err(_) => match Hash::from_base64(space) {
-                                Some(space_hash) => {
-                                    match SpaceRecord::find(state.database.clone(), &space_hash) {
-                                        Ok(Some(record)) => Ok(record),
-                                        Ok(None) => Err(anyhow::anyhow!("there's no space record with such root block hash")),
-                                        Err(err) => Err(anyhow::anyhow!(err).context("failed to find space record"))
+                                Err(_) => match Hash::from_base64(space) {
+                                    Some(space_hash) => {
+                                        match SpaceRecord::find(database, &space_hash) {
+                                            Ok(Some(record)) => Ok(record),
+                                            Ok(None) => Err(anyhow::anyhow!("there's no space record with such root block hash")),
+                                            Err(err) => Err(anyhow::anyhow!(err).context("failed to find space record"))
+                                        }
                                     }
-                                }
 
-                                None => Err(anyhow::anyhow!("invalid space root block hash format"))
+                                    None => Err(anyhow::anyhow!("invalid space root block hash format"))
+                                }
                             }
-                        };
+                        }).await;
 
                         let _ = sender.send(space);
                     }
@@ -142,18 +499,31 @@ pub fn run_actions_handler(
                         shards,
                         viewer
                     } => {
-                        let mut lock = state.connection.write();
+                        let space_id = space.id();
 
-                        // Destroy previous connection.
-                        if let Some(prev_connection) = &*lock {
-                            prev_connection.sync_task.abort();
-                        }
+                        let info = state.database.spawn({
+                            let space = space.clone();
+
+                            move |_| Ok(space.load()?)
+                        }).await;
+
+                        let title = match info {
+                            Ok(info) if !info.title.is_empty() => {
+                                format!("{} {} {}", info.emoji(), info.shortname(), info.title)
+                            }
+
+                            Ok(info) => format!("{} {}", info.emoji(), info.shortname()),
+
+                            Err(_) => format!("#{space_id}")
+                        };
 
                         // Spawn new connection.
                         let (sender, mut receiver) = unbounded_channel();
 
                         let mut sender = Some(sender);
 
+                        let sync_action_sender = action_sender.clone();
+
                         let sync_task = runtime.spawn(crate::client::run(
                             state.database.clone(),
                             viewer,
@@ -179,54 +549,348 @@ pub fn run_actions_handler(
 
                                     Update::NewEvent {
                                         block_hash: _,
-                                        transaction_hash: _,
-                                        block_timestamp: _
+                                        transaction_hash,
+                                        block_timestamp: _,
+                                        event
                                     } => {
                                         sender = None;
+
+                                        let room_name = match event {
+                                            Events::PublicRoomMessage(event) => Some(event.room_name().to_string()),
+                                            _ => None
+                                        };
+
+                                        let _ = sync_action_sender.send(Action::SpaceEvent {
+                                            space_id,
+                                            transaction_hash,
+                                            room_name
+                                        });
                                     }
                                 }
                             }
                         ));
 
-                        lock.replace(SpaceConnection {
+                        let outbox_action_sender = action_sender.clone();
+                        let outbox_connections = state.connections.clone();
+
+                        let outbox_task = runtime.spawn(crate::outbox::run(
+                            state.database.clone(),
+                            space_id,
+                            client.clone(),
+                            move || {
+                                outbox_connections.read()
+                                    .get(space_id)
+                                    .map(SpaceConnection::announce_shards)
+                                    .unwrap_or_default()
+                            },
+                            move |update| {
+                                match update {
+                                    OutboxUpdate::Failed { transaction_hash } => {
+                                        let _ = outbox_action_sender.send(Action::TerminalPush(format!(
+                                            "outbox: transaction {} was not included into the blockchain",
+                                            transaction_hash.to_base64()
+                                        )));
+                                    }
+
+                                    OutboxUpdate::Error(err) => {
+                                        let _ = outbox_action_sender.send(Action::TerminalPush(format!(
+                                            "outbox: {err}"
+                                        )));
+
+                                        return;
+                                    }
+
+                                    OutboxUpdate::Announced { .. } => ()
+                                }
+
+                                let _ = outbox_action_sender.send(Action::RefreshRoom);
+                            }
+                        ));
+
+                        let pool_connections = state.connections.clone();
+                        let shards_connections = state.connections.clone();
+                        let shards_action_sender = action_sender.clone();
+
+                        let shards_task = runtime.spawn(crate::shards::run(
+                            space.clone(),
+                            client.clone(),
+                            move || {
+                                pool_connections.read()
+                                    .get(space_id)
+                                    .map(|connection| connection.shards_pool.clone())
+                            },
+                            move |update| {
+                                let mut connections = shards_connections.write();
+
+                                let Some(connection) = connections.get_mut(space_id) else {
+                                    return;
+                                };
+
+                                match update {
+                                    ShardsUpdate::Pool(pool) => connection.shards_pool = pool,
+
+                                    ShardsUpdate::Probed { address, latency } => {
+                                        connection.shard_scores.record(address, latency);
+                                    }
+
+                                    ShardsUpdate::Discovered(address) => {
+                                        let _ = shards_action_sender.send(Action::TerminalPush(format!(
+                                            "shards: discovered new shard {address}"
+                                        )));
+                                    }
+
+                                    ShardsUpdate::Error(err) => {
+                                        let _ = shards_action_sender.send(Action::TerminalPush(format!(
+                                            "shards: {err}"
+                                        )));
+                                    }
+                                }
+                            }
+                        ));
+
+                        let mut connections = state.connections.write();
+
+                        // Remember opened room of the previously focused
+                        // space to restore it later.
+                        let last_room = state.room.write()
+                            .take()
+                            .map(|room| room.room);
+
+                        connections.remember_room(last_room);
+
+                        // Replaces previous connection to the same space
+                        // if there was one.
+                        connections.insert(SpaceConnection {
                             space,
                             client,
                             shards_pool: shards,
                             sync_task,
-                            identity: secret_key
+                            outbox_task,
+                            shards_task,
+                            shard_scores: ShardScores::default(),
+                            identity: secret_key,
+                            title,
+                            unread: 0,
+                            last_room: None
+                        });
+
+                        drop(connections);
+
+                        // Reopen the room if we reconnected to the same space.
+                        if let Err(err) = restore_room(&state).await {
+                            state.terminal_widget.write().push(format!("failed to open room: {err}"));
+                        }
+
+                        let _ = updates_sender.send(());
+
+                        // Report verification progress without blocking other
+                        // actions.
+                        runtime.spawn({
+                            let state = state.clone();
+                            let action_sender = action_sender.clone();
+                            let updates_sender = updates_sender.clone();
+
+                            async move {
+                                let mut i = 0u64;
+
+                                while let Some(update) = receiver.recv().await {
+                                    let (
+                                        block_hash,
+                                        transaction_hash,
+                                        _block_timestamp,
+                                        estimated_progress
+                                    ) = update;
+
+                                    i += 1;
+
+                                    let line = format!(
+                                        "connect: [{i:6}] verified tr {}, block {}",
+                                        transaction_hash.to_base64(),
+                                        block_hash.to_base64()
+                                    );
+
+                                    let _ = action_sender.send(Action::TerminalPush(line));
+
+                                    let width = (state.terminal_widget.read().width as usize).saturating_sub(20);
+                                    let offset = ((estimated_progress * width as f32).round() as usize).min(width);
+
+                                    let line = format!(
+                                        "connect: |{}{}| {:.2}%",
+                                        "#".repeat(offset),
+                                        " ".repeat(width - offset),
+                                        estimated_progress
+                                    );
+
+                                    let _ = action_sender.send(Action::TerminalSetCurrentLine(line));
+
+                                    let _ = updates_sender.send(());
+                                }
+                            }
                         });
+                    }
+
+                    Action::Disconnect => {
+                        let connection = state.connections.write().remove_focused();
+
+                        if let Some(connection) = connection {
+                            state.terminal_widget.write().push(format!(
+                                "disconnected from {}",
+                                connection.title
+                            ));
+                        }
 
-                        let mut i = 0u64;
+                        if let Err(err) = restore_room(&state).await {
+                            state.terminal_widget.write().push(format!("failed to open room: {err}"));
+                        }
 
-                        while let Some(update) = receiver.recv().await {
-                            let (
-                                block_hash,
-                                transaction_hash,
-                                _block_timestamp,
-                                estimated_progress
-                            ) = update;
+                        let _ = updates_sender.send(());
+                    }
 
-                            i += 1;
+                    Action::Focus(index) => {
+                        let mut connections = state.connections.write();
 
-                            let line = format!(
-                                "connect: [{i:6}] verified tr {}, block {}",
-                                transaction_hash.to_base64(),
-                                block_hash.to_base64()
-                            );
+                        if connections.focused_index() == Some(index) {
+                            continue;
+                        }
 
-                            let _ = action_sender.send(Action::TerminalPush(line));
+                        // Remember opened room of the previously focused
+                        // space to restore it later.
+                        let last_room = state.room.read()
+                            .as_ref()
+                            .map(|room| room.room.clone());
 
-                            let width = state.terminal_widget.read().width as usize - 20;
-                            let offset = (estimated_progress * width as f32).round() as usize;
+                        connections.remember_room(last_room);
 
-                            let line = format!(
-                                "connect: |{}{}| {:.2}%",
-                                "#".repeat(offset),
-                                " ".repeat(width - offset),
-                                estimated_progress
-                            );
+                        let is_focused = connections.focus(index);
 
-                            let _ = action_sender.send(Action::TerminalSetCurrentLine(line));
+                        drop(connections);
+
+                        if is_focused && let Err(err) = restore_room(&state).await {
+                            state.terminal_widget.write().push(format!("failed to open room: {err}"));
+                        }
+
+                        let _ = updates_sender.send(());
+                    }
+
+                    Action::SpaceEvent { space_id, transaction_hash, room_name } => {
+                        // Only messages change the opened room and the
+                        // unread counters.
+                        let Some(room_name) = room_name else {
+                            continue;
+                        };
+
+                        let mut connections = state.connections.write();
+
+                        let is_focused = connections.focused()
+                            .is_some_and(|connection| connection.space.id() == space_id);
+
+                        if is_focused {
+                            let is_opened = state.room.read()
+                                .as_ref()
+                                .is_some_and(|room| room.space_id == space_id && room.name == room_name);
+
+                            if is_opened {
+                                let _ = action_sender.send(Action::Reconcile(transaction_hash));
+                            }
+                        }
+
+                        else if connections.add_unread(space_id) {
+                            let _ = updates_sender.send(());
+                        }
+                    }
+
+                    Action::OpenRoom(room) => {
+                        let offset = state.offset();
+
+                        match state.database.spawn(move |_| RoomView::load(room, offset)).await {
+                            Ok(room) => {
+                                state.room.write().replace(room);
+                            }
+
+                            Err(err) => {
+                                state.terminal_widget.write().push(format!("failed to open room: {err}"));
+                            }
+                        }
+
+                        let _ = updates_sender.send(());
+                    }
+
+                    Action::OpenRoomAt { room, message_id } => {
+                        let offset = state.offset();
+
+                        let room = state.database.spawn(move |_| {
+                            RoomView::load_at(room, Some(message_id), offset)
+                        }).await;
+
+                        match room {
+                            Ok(room) => {
+                                state.room.write().replace(room);
+                            }
+
+                            Err(err) => {
+                                state.terminal_widget.write().push(format!("failed to open room: {err}"));
+                            }
+                        }
+
+                        let _ = updates_sender.send(());
+                    }
+
+                    Action::CloseRoom => {
+                        state.room.write().take();
+
+                        let _ = updates_sender.send(());
+                    }
+
+                    Action::LocalEcho { space_id, room_name, message } => {
+                        if let Some(room) = &mut *state.room.write() &&
+                            room.space_id == space_id &&
+                            room.name == room_name
+                        {
+                            room.push_local_echo(message);
+                        }
+
+                        let _ = updates_sender.send(());
+                    }
+
+                    Action::Reconcile(transaction_hash) => {
+                        if let Some(room) = &mut *state.room.write() {
+                            room.reconcile(&transaction_hash);
+                        }
+
+                        if !refresh_queued {
+                            refresh_queued = true;
+
+                            let _ = action_sender.send(Action::RefreshRoom);
+                        }
+                    }
+
+                    Action::RefreshRoom => {
+                        refresh_queued = false;
+
+                        let room = state.room.read().clone();
+
+                        if let Some(mut room) = room {
+                            let room = state.database.spawn(move |_| {
+                                room.reload()?;
+
+                                Ok(room)
+                            }).await;
+
+                            match room {
+                                Ok(room) => {
+                                    let mut lock = state.room.write();
+
+                                    // Do not reopen the room if it was closed
+                                    // or changed while we were reloading it.
+                                    if lock.as_ref().is_some_and(|current| current.room.id() == room.room.id()) {
+                                        lock.replace(room);
+                                    }
+                                }
+
+                                Err(err) => {
+                                    state.terminal_widget.write().push(format!("failed to reload room: {err}"));
+                                }
+                            }
 
                             let _ = updates_sender.send(());
                         }
@@ -238,3 +902,69 @@ pub fn run_actions_handler(
 
     (action_sender, updates_receiver)
 }
+
+#[tokio::test]
+async fn test_unread() -> anyhow::Result<()> {
+    let database = Database::open_in_memory()?;
+
+    let connection = |space_id| SpaceConnection {
+        client: Client::default(),
+        shards_pool: ShardsPool::default(),
+        sync_task: tokio::spawn(async { Ok(()) }),
+        outbox_task: tokio::spawn(async { Ok(()) }),
+        shards_task: tokio::spawn(async { Ok(()) }),
+        shard_scores: ShardScores::default(),
+        space: SpaceRecord::open_raw(database.clone(), space_id),
+        identity: SecretKey::random(&mut crate::utils::get_rng()),
+        title: format!("space {space_id}"),
+        unread: 0,
+        last_room: None
+    };
+
+    let mut connections = SpaceConnections::default();
+
+    connections.insert(connection(1));
+    connections.insert(connection(2));
+
+    // Messages of the focused space are not counted.
+    assert!(connections.add_unread(1));
+    assert!(connections.add_unread(1));
+    assert!(!connections.add_unread(2));
+    assert!(!connections.add_unread(3));
+
+    let unread = |connections: &SpaceConnections| {
+        connections.iter()
+            .map(|connection| connection.unread)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(unread(&connections), [2, 0]);
+
+    // Focusing the space resets its counter.
+    assert!(connections.focus(0));
+    assert!(!connections.focus(2));
+
+    assert_eq!(unread(&connections), [0, 0]);
+
+    assert!(connections.add_unread(2));
+    assert!(!connections.add_unread(1));
+
+    assert_eq!(unread(&connections), [0, 1]);
+
+    // Reconnecting to the space keeps its position and remembered room but
+    // resets the counter since the space is focused.
+    connections.focus(1);
+    connections.remember_room(Some(PublicRoomRecord::open_raw(database.clone(), 1)));
+    connections.insert(connection(2));
+
+    assert_eq!(connections.focused_index(), Some(1));
+    assert_eq!(unread(&connections), [0, 0]);
+
+    let last_room = connections.focused()
+        .and_then(|connection| connection.last_room.as_ref())
+        .map(PublicRoomRecord::id);
+
+    assert_eq!(last_room, Some(1));
+
+    Ok(())
+}
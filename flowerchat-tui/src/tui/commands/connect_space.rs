@@ -27,15 +27,9 @@ use crate::tui::app::Action;
 
 pub async fn run(
     space: impl ToString,
-    identity: impl AsRef<[u8]>,
+    identity: SecretKey,
     output: impl Fn(Action)
 ) {
-    let Some(identity) = SecretKey::from_base64(identity) else {
-        output(Action::TerminalPush(String::from("invalid identity format: base64 secret key expected")));
-
-        return;
-    };
-
     let (send, recv) = oneshot_channel();
 
     output(Action::RequestSpaceRecord(space.to_string(), send));
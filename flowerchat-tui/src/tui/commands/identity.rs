@@ -0,0 +1,290 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// flowerchat
+// Copyright (C) 2025  Nikita Podvirnyi <krypt0nn@vk.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use zeroize::Zeroizing;
+
+use libflowerpot::crypto::*;
+
+use crate::identities::{self, Identity};
+use crate::tui::app::{AppState, Action, SecretPrompt};
+use crate::utils::{get_rng, make_table};
+
+/// Apply change to the current identities list, encrypt it and store it in
+/// the app state. Change returns message printed on success.
+async fn update(
+    state: &AppState,
+    change: impl FnOnce(&mut Vec<Identity>) -> anyhow::Result<String> + Send + 'static,
+    output: &impl Fn(Action)
+) {
+    let result = state.modify_identities(move |unlocked| {
+        let mut list = unlocked.identities()
+            .map(<[_]>::to_vec)
+            .ok_or_else(|| anyhow::anyhow!("identities are locked, use `unlock` first"))?;
+
+        let message = change(&mut list)?;
+
+        unlocked.update(list)?;
+
+        Ok(message)
+    }).await;
+
+    match result {
+        Ok(message) => output(Action::TerminalPush(message)),
+        Err(err) => output(Action::TerminalPush(format!("failed to update identities: {err}")))
+    }
+}
+
+/// Add identity to the list unless there's another one with the same secret
+/// key.
+fn add(list: &mut Vec<Identity>, identity: Identity) -> anyhow::Result<()> {
+    if list.iter().any(|stored| stored.secret_key() == identity.secret_key()) {
+        anyhow::bail!("identity with this secret key already exists");
+    }
+
+    list.push(identity);
+
+    Ok(())
+}
+
+/// Find identity in the list returning an error if it doesn't exist.
+fn find(list: &[Identity], identity: &str) -> anyhow::Result<usize> {
+    identities::find(list, identity)
+        .ok_or_else(|| anyhow::anyhow!("identity not found: {identity}"))
+}
+
+pub async fn run(
+    state: AppState,
+    mut command: impl Iterator<Item = String>,
+    output: impl Fn(Action)
+) {
+    let list = state.identities.read()
+        .identities()
+        .map(<[_]>::to_vec);
+
+    let Some(list) = list else {
+        output(Action::TerminalPush(String::from(
+            "identities are locked, use `unlock` first"
+        )));
+
+        return;
+    };
+
+    let identity = |identity: Option<String>| {
+        if identity.is_none() {
+            output(Action::TerminalPush(String::from(
+                "identity index, title or shortname is not provided"
+            )));
+        }
+
+        identity
+    };
+
+    match command.next().as_deref() {
+        Some("list") => {
+            let rows = list.iter()
+                .enumerate()
+                .map(|(i, identity)| [
+                    (i + 1).to_string(),
+                    identity.title().clone(),
+                    format!("{} {}", identity.emoji(), identity.shortname()),
+                    identity.public_key().to_base64(),
+                    identity.created_at().date().to_string()
+                ])
+                .collect::<Vec<_>>();
+
+            if rows.is_empty() {
+                output(Action::TerminalPush(String::from("no identities")));
+
+                return;
+            }
+
+            output(Action::TerminalPush(make_table(
+                ["#", "Title", "Shortname", "Public key", "Created at"],
+                rows
+            )));
+        }
+
+        Some("create") => {
+            let title = command.collect::<Vec<String>>().join(" ");
+
+            if title.is_empty() {
+                output(Action::TerminalPush(String::from("identity title is not provided")));
+
+                return;
+            }
+
+            let identity = Identity::new(title, SecretKey::random(&mut get_rng()));
+
+            update(&state, move |list| {
+                let message = format!(
+                    "created identity {} {} {}",
+                    identity.emoji(),
+                    identity.shortname(),
+                    identity.title()
+                );
+
+                list.push(identity);
+
+                Ok(message)
+            }, &output).await;
+        }
+
+        Some("rename") => {
+            let Some(identity) = identity(command.next()) else {
+                return;
+            };
+
+            let title = command.collect::<Vec<String>>().join(" ");
+
+            if title.is_empty() {
+                output(Action::TerminalPush(String::from("identity title is not provided")));
+
+                return;
+            }
+
+            update(&state, move |list| {
+                let index = find(list, &identity)?;
+
+                list[index].set_title(title);
+
+                Ok(String::from("identity renamed"))
+            }, &output).await;
+        }
+
+        Some("remove") => {
+            let Some(identity) = identity(command.next()) else {
+                return;
+            };
+
+            update(&state, move |list| {
+                let index = find(list, &identity)?;
+
+                list.remove(index);
+
+                Ok(String::from("identity removed"))
+            }, &output).await;
+        }
+
+        Some("export") => {
+            let Some(identity) = identity(command.next()) else {
+                return;
+            };
+
+            let index = match find(&list, &identity) {
+                Ok(index) => index,
+                Err(err) => {
+                    output(Action::TerminalPush(err.to_string()));
+
+                    return;
+                }
+            };
+
+            output(Action::TerminalPush(String::from(
+                "secret key gives full control over the identity, don't share it with anyone!"
+            )));
+
+            output(Action::TerminalPush(list[index].secret_key().to_base64()));
+        }
+
+        Some("mnemonic") => {
+            let Some(identity) = identity(command.next()) else {
+                return;
+            };
+
+            let index = match find(&list, &identity) {
+                Ok(index) => index,
+                Err(err) => {
+                    output(Action::TerminalPush(err.to_string()));
+
+                    return;
+                }
+            };
+
+            match identities::secret_key_to_mnemonic(list[index].secret_key()) {
+                Ok(phrase) => {
+                    output(Action::TerminalPush(String::from(
+                        "mnemonic phrase gives full control over the identity, write it down and don't share it with anyone!"
+                    )));
+
+                    output(Action::TerminalPush(phrase));
+                }
+
+                Err(err) => output(Action::TerminalPush(format!("failed to encode mnemonic phrase: {err}")))
+            }
+        }
+
+        Some(subcommand @ ("import" | "restore")) => {
+            let title = command.collect::<Vec<String>>().join(" ");
+
+            if title.is_empty() {
+                output(Action::TerminalPush(String::from("identity title is not provided")));
+
+                return;
+            }
+
+            if subcommand == "import" {
+                state.ask_secret(SecretPrompt::SecretKey(title), "enter secret key of the identity", output);
+            } else {
+                state.ask_secret(SecretPrompt::Mnemonic(title), "enter mnemonic phrase of the identity", output);
+            }
+        }
+
+        Some(_) => output(Action::TerminalPush(String::from("unknown subcommand"))),
+        _ => output(Action::TerminalPush(String::from("not subcommand provided")))
+    }
+}
+
+/// Add identity with secret key or mnemonic phrase entered with a hidden
+/// input after the `identity import` or `identity restore` command.
+pub async fn add_secret(
+    state: AppState,
+    prompt: SecretPrompt,
+    secret: Zeroizing<String>,
+    output: impl Fn(Action)
+) {
+    let (title, secret_key, message) = match prompt {
+        SecretPrompt::SecretKey(title) => {
+            let Some(secret_key) = SecretKey::from_base64(secret.trim()) else {
+                output(Action::TerminalPush(String::from("invalid secret key format")));
+
+                return;
+            };
+
+            (title, secret_key, "identity imported")
+        }
+
+        SecretPrompt::Mnemonic(title) => {
+            match identities::secret_key_from_mnemonic(&secret) {
+                Ok(secret_key) => (title, secret_key, "identity restored"),
+                Err(err) => {
+                    output(Action::TerminalPush(err.to_string()));
+
+                    return;
+                }
+            }
+        }
+
+        SecretPrompt::Passphrase => return
+    };
+
+    update(&state, move |list| {
+        add(list, Identity::new(title, secret_key))?;
+
+        Ok(String::from(message))
+    }, &output).await;
+}
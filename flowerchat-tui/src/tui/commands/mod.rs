@@ -18,10 +18,23 @@
 
 mod print_help;
 mod print_spaces;
+mod space_import;
+mod space_share;
+mod print_shards;
 mod connect_space;
 mod room_list;
 mod room_create;
+mod room_open;
+mod room_send;
+mod search;
+mod safety_number;
+mod send_event;
 
+pub mod identity;
+pub mod unlock;
+
+use crate::identities;
+use crate::shards;
 use crate::tui::app::{AppState, Action};
 
 pub async fn run_command(
@@ -29,7 +42,7 @@ pub async fn run_command(
     state: AppState,
     output: impl Fn(Action)
 ) {
-    let is_connected = state.connection.read().is_some();
+    let is_connected = !state.connections.read().is_empty();
 
     let mut command = command.into_iter();
 
@@ -54,18 +67,151 @@ pub async fn run_command(
             }
 
             Some("open") => {
+                let Some(name) = command.next() else {
+                    output(Action::TerminalPush(String::from(
+                        "public room name is not provided"
+                    )));
 
+                    return;
+                };
+
+                room_open::run(state, name, output).await;
             }
 
+            Some("close") => output(Action::CloseRoom),
+
             Some(_) => output(Action::TerminalPush(String::from("unknown subcommand"))),
             _ => output(Action::TerminalPush(String::from("not subcommand provided")))
         }
 
-        // Not connected
+        Some("send") => {
+            let message = command.collect::<Vec<String>>().join(" ");
+
+            if message.is_empty() {
+                output(Action::TerminalPush(String::from(
+                    "message is not provided"
+                )));
+
+                return;
+            }
+
+            room_send::run(state, message, output).await;
+        }
+
+        Some("search") => {
+            let query = command.collect::<Vec<String>>();
+
+            if query.is_empty() {
+                output(Action::TerminalPush(String::from(
+                    "search query is not provided"
+                )));
+
+                return;
+            }
+
+            search::run(state, query, output).await;
+        }
+
+        Some("jump") => {
+            let Some(message_id) = command.next().and_then(|id| id.parse::<i64>().ok()) else {
+                output(Action::TerminalPush(String::from(
+                    "message id is not provided"
+                )));
+
+                return;
+            };
+
+            search::jump(state, message_id, output).await;
+        }
+
+        Some("safety") => {
+            let Some(user) = command.next() else {
+                output(Action::TerminalPush(String::from(
+                    "user nickname or public key is not provided"
+                )));
 
-        Some("spaces") if !is_connected => print_spaces::run(state, output).await,
+                return;
+            };
 
-        Some("connect") if !is_connected => {
+            safety_number::run(state, user, output).await;
+        }
+
+        Some("shards") => print_shards::run(state, output),
+
+        Some("switch") => {
+            let index = command.next()
+                .and_then(|index| index.parse::<usize>().ok())
+                .filter(|index| *index > 0);
+
+            let Some(index) = index else {
+                output(Action::TerminalPush(String::from(
+                    "space number is not provided"
+                )));
+
+                return;
+            };
+
+            output(Action::Focus(index - 1));
+        }
+
+        Some("disconnect") => output(Action::Disconnect),
+
+        // Always available
+
+        Some("spaces") => print_spaces::run(state, output).await,
+
+        Some("import") => {
+            let Some(link) = command.next() else {
+                output(Action::TerminalPush(String::from(
+                    "share link is not provided"
+                )));
+
+                return;
+            };
+
+            space_import::run(state, link, output).await;
+        }
+
+        Some("share") => {
+            let Some(space) = command.next() else {
+                output(Action::TerminalPush(String::from(
+                    "space id or root block hash is not provided"
+                )));
+
+                return;
+            };
+
+            let mut max_shards = shards::SHARE_LINK_SHARDS;
+            let mut public_only = false;
+
+            for arg in command {
+                match arg.parse::<usize>() {
+                    Ok(value) => max_shards = value,
+
+                    Err(_) if arg == "public" => public_only = true,
+
+                    Err(_) => {
+                        output(Action::TerminalPush(format!("unknown argument: {arg}")));
+
+                        return;
+                    }
+                }
+            }
+
+            space_share::run(state, space, max_shards, public_only, output).await;
+        }
+
+        Some("identity") => identity::run(state, command, output).await,
+
+        Some("unlock") => unlock::prompt(state, output),
+
+        Some("lock") => {
+            state.identities.write().lock();
+
+            output(Action::TerminalPush(String::from("identities are locked")));
+        }
+
+        Some("connect") => {
             let Some(space) = command.next() else {
                 output(Action::TerminalPush(String::from(
                     "space id or root block hash is not provided"
@@ -76,13 +222,28 @@ pub async fn run_command(
 
             let Some(identity) = command.next() else {
                 output(Action::TerminalPush(String::from(
-                    "identity (secret key) is not provided"
+                    "identity index, title or shortname is not provided"
                 )));
 
                 return;
             };
 
-            connect_space::run(space, identity, output).await
+            let secret_key = state.identities.read()
+                .identities()
+                .map(|identities| {
+                    identities::find(identities, &identity)
+                        .map(|index| identities[index].secret_key().clone())
+                });
+
+            match secret_key {
+                Some(Some(secret_key)) => connect_space::run(space, secret_key, output).await,
+
+                Some(None) => output(Action::TerminalPush(format!("identity not found: {identity}"))),
+
+                None => output(Action::TerminalPush(String::from(
+                    "identities are locked, use `unlock` first"
+                )))
+            }
         }
 
         Some(_) | None => print_help::run(is_connected, output)
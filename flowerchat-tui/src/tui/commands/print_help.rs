@@ -25,13 +25,47 @@ pub fn run(is_connected: bool, output: impl Fn(Action)) {
             ["help", "list available commands"],
             ["room list", "list all existing rooms"],
             ["room create <name>", "create new room"],
-            ["room open <name>", "open existing room"]
+            ["room open <name>", "open existing room"],
+            ["room close", "close opened room"],
+            ["send <message>", "send message to the opened room"],
+            ["search <query>", "search messages (filters: in:room, from:user, before:date, after:date)"],
+            ["jump <#>", "open the found message in its room"],
+            ["safety <user>", "show safety number with the user (nickname or public key)"],
+            ["shards", "list shards of the focused space"],
+            ["spaces", "list available spaces"],
+            ["import <link>", "import space from its share link or flowerchat:// uri"],
+            ["share <space> [n] [public]", "make space share link with n healthiest (public) shards"],
+            ["connect <space> <identity>", "connect to another space (identity index, title or shortname)"],
+            ["switch <n>", "focus connected space (or press F1-F9)"],
+            ["disconnect", "close connection to the focused space"],
+            ["identity list", "list unlocked identities"],
+            ["identity create <title>", "create new identity"],
+            ["identity rename <identity> <title>", "change identity title"],
+            ["identity remove <identity>", "remove identity"],
+            ["identity export <identity>", "print identity secret key"],
+            ["identity import <title>", "import identity from its secret key"],
+            ["identity mnemonic <identity>", "print identity mnemonic phrase"],
+            ["identity restore <title>", "restore identity from its mnemonic phrase"],
+            ["unlock", "unlock identities with the passphrase"],
+            ["lock", "forget unlocked identities"]
         ])
     } else {
         make_table(["Command", "Description"], [
             ["help", "list available commands"],
             ["spaces", "list available spaces"],
-            ["connect <space> <identity>", "connect to space"]
+            ["import <link>", "import space from its share link or flowerchat:// uri"],
+            ["share <space> [n] [public]", "make space share link with n healthiest (public) shards"],
+            ["connect <space> <identity>", "connect to space (identity index, title or shortname)"],
+            ["identity list", "list unlocked identities"],
+            ["identity create <title>", "create new identity"],
+            ["identity rename <identity> <title>", "change identity title"],
+            ["identity remove <identity>", "remove identity"],
+            ["identity export <identity>", "print identity secret key"],
+            ["identity import <title>", "import identity from its secret key"],
+            ["identity mnemonic <identity>", "print identity mnemonic phrase"],
+            ["identity restore <title>", "restore identity from its mnemonic phrase"],
+            ["unlock", "unlock identities with the passphrase"],
+            ["lock", "forget unlocked identities"]
         ])
     };
 
@@ -0,0 +1,64 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// flowerchat
+// Copyright (C) 2025  Nikita Podvirnyi <krypt0nn@vk.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::tui::app::{AppState, Action};
+use crate::utils::make_table;
+
+pub fn run(state: AppState, output: impl Fn(Action)) {
+    let shards = state.with_focused(|connection| {
+        connection.shards_pool.active()
+            .map(|address| (address, "active"))
+            .chain(connection.shards_pool.inactive().map(|address| (address, "inactive")))
+            .map(|(address, status)| {
+                let score = connection.shard_scores.get(address);
+
+                let status = match score {
+                    Some(score) if score.is_demoted() => format!("{status}, demoted"),
+                    _ => status.to_string()
+                };
+
+                let latency = score.and_then(|score| score.latency)
+                    .map(|latency| format!("{} ms", latency.as_millis()))
+                    .unwrap_or_else(|| String::from("-"));
+
+                let failures = score.map(|score| score.failures)
+                    .unwrap_or_default()
+                    .to_string();
+
+                [address.clone(), status, latency, failures]
+            })
+            .collect::<Vec<_>>()
+    });
+
+    let Some(shards) = shards else {
+        output(Action::TerminalPush(String::from("not connected to any space")));
+
+        return;
+    };
+
+    if shards.is_empty() {
+        output(Action::TerminalPush(String::from("no known shards")));
+
+        return;
+    }
+
+    output(Action::TerminalPush(make_table(
+        ["Address", "Status", "Latency", "Failures"],
+        shards
+    )));
+}
@@ -25,40 +25,40 @@ pub async fn run(
 ) {
     let mut spaces_data = Vec::new();
 
-    for space in state.database.spaces() {
-        let title = match space.title() {
-            Ok(title) if title.is_empty() => String::from("<unknown>"),
-            Ok(title) => title,
-            Err(err) => {
-                output(Action::TerminalPush(format!("failed to get space title: {err}")));
-
-                return;
-            }
+    for (space, info) in state.database.spaces() {
+        let title = if info.title.is_empty() {
+            String::from("<unknown>")
+        } else {
+            info.title
         };
 
-        let root_block = match space.root_block() {
-            Ok(root_block) => root_block,
-            Err(err) => {
-                output(Action::TerminalPush(format!("failed to get space root block: {err}")));
+        let connections = state.connections.read();
 
-                return;
-            }
-        };
+        let status = connections.iter()
+            .enumerate()
+            .find(|(_, connection)| connection.space.id() == space.id())
+            .map(|(i, connection)| {
+                let mut status = format!("F{}", i + 1);
 
-        let author = match space.author() {
-            Ok(author) => author,
-            Err(err) => {
-                output(Action::TerminalPush(format!("failed to get space author: {err}")));
+                if connections.focused_index() == Some(i) {
+                    status.push_str(" focused");
+                }
 
-                return;
-            }
-        };
+                if connection.unread > 0 {
+                    status.push_str(&format!(", {} unread", connection.unread));
+                }
+
+                status
+            })
+            .unwrap_or_default();
+
+        drop(connections);
 
         let space_id = space.id().to_string();
-        let root_block = root_block.to_base64();
-        let public_key = author.to_base64();
+        let root_block = info.root_block.to_base64();
+        let public_key = info.author.to_base64();
 
-        spaces_data.push([space_id, title, root_block, public_key]);
+        spaces_data.push([space_id, title, root_block, public_key, status]);
     }
 
     if spaces_data.is_empty() {
@@ -66,7 +66,7 @@ pub async fn run(
     }
 
     output(Action::TerminalPush(make_table(
-        ["#", "Title", "Root block", "Public key"],
+        ["#", "Title", "Root block", "Public key", "Status"],
         spaces_data
     )));
 }
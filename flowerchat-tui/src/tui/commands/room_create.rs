@@ -16,24 +16,20 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use rand_chacha::rand_core::RngCore;
-
-use libflowerpot::transaction::Transaction;
-
 use flowerchat_protocol::types::room_name::RoomName;
-use flowerchat_protocol::events::{Event, Events};
 use flowerchat_protocol::events::rooms::create_public::CreatePublicRoomEvent;
 
 use crate::database::public_room::PublicRoomRecord;
 use crate::tui::app::{AppState, Action};
-use crate::utils::get_rng;
+
+use super::send_event;
 
 pub async fn run(
     state: AppState,
     name: impl ToString,
     output: impl Fn(Action)
 ) {
-    let Some(connection) = &*state.connection.read() else {
+    let Some(space_id) = state.with_focused(|connection| connection.space.id()) else {
         output(Action::TerminalPush(String::from("Not connected")));
 
         return;
@@ -45,76 +41,17 @@ pub async fn run(
         return;
     };
 
-    let database = state.database.clone();
-
-    match PublicRoomRecord::find(database, connection.space.id(), &name) {
-        Ok(None) => {
-            output(Action::TerminalSetCurrentLine(String::from("Building transaction...")));
-
-            let event = Events::from(CreatePublicRoomEvent::from(name));
-
-            let mut data = Vec::new();
-
-            if let Err(err) = event.serialize(&mut data) {
-                output(Action::TerminalSetCurrentLine(String::new()));
-                output(Action::TerminalPush(format!("Failed to create event: {err}")));
-
-                return;
-            };
-
-            let transaction = Transaction::create(
-                &connection.identity,
-                get_rng().next_u64(),
-                data
-            );
+    let room = state.database.spawn({
+        let name = name.to_string();
 
-            output(Action::TerminalSetCurrentLine(String::new()));
+        move |database| Ok(PublicRoomRecord::find(database, space_id, name)?)
+    }).await;
 
-            let transaction = match transaction {
-                Ok(transaction) => transaction,
-                Err(err) => {
-                    output(Action::TerminalPush(format!("Failed to create transaction: {err}")));
-
-                    return;
-                }
-            };
-
-            output(Action::TerminalPush(format!(
-                "Building transaction... {}",
-                transaction.hash().to_base64()
-            )));
-
-            let shards = connection.shards_pool.active()
-                .map(String::from)
-                .collect::<Vec<String>>();
-
-            output(Action::TerminalSetCurrentLine(format!(
-                "Announcing transaction to {} active shards...",
-                shards.len()
-            )));
-
-            let result = connection.client.put_transaction(
-                &shards,
-                &transaction
-            ).await;
-
-            output(Action::TerminalSetCurrentLine(String::new()));
-
-            if let Err(err) = result {
-                output(Action::TerminalPush(format!(
-                    "Announcing transaction to {} active shards... Error",
-                    shards.len()
-                )));
-
-                output(Action::TerminalPush(format!("Failed to announce transaction: {err}")));
-            }
+    match room {
+        Ok(None) => {
+            let event = CreatePublicRoomEvent::from(name);
 
-            else {
-                output(Action::TerminalPush(format!(
-                    "Announcing transaction to {} active shards... Done",
-                    shards.len()
-                )));
-            }
+            send_event::run(state, event, output).await;
         }
 
         Ok(Some(_)) => output(Action::TerminalPush(String::from("Room with such name already exists"))),
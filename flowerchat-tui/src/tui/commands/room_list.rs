@@ -20,32 +20,15 @@ use crate::tui::app::{AppState, Action};
 use crate::utils::make_table;
 
 pub fn run(state: AppState, output: impl Fn(Action)) {
-    let Some(connection) = &*state.connection.read() else {
+    let Some(space) = state.with_focused(|connection| connection.space.clone()) else {
         output(Action::TerminalPush(String::from("not connected to any space")));
 
         return;
     };
 
-    let mut rooms = Vec::new();
-
-    for room in connection.space.public_rooms() {
-        let room_name = match room.name() {
-            Ok(room_name) => room_name,
-            Err(err) => {
-                output(Action::TerminalPush(format!(
-                    "failed to query room #{} name: {err}",
-                    room.id()
-                )));
-
-                return;
-            }
-        };
-
-        rooms.push([
-            room.id().to_string(),
-            room_name
-        ]);
-    }
+    let rooms = space.public_rooms()
+        .map(|(room, info)| [room.id().to_string(), info.name])
+        .collect::<Vec<_>>();
 
     if rooms.is_empty() {
         return;
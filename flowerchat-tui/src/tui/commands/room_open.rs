@@ -0,0 +1,44 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// flowerchat
+// Copyright (C) 2025  Nikita Podvirnyi <krypt0nn@vk.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::database::public_room::PublicRoomRecord;
+use crate::tui::app::{AppState, Action};
+
+pub async fn run(
+    state: AppState,
+    name: impl ToString,
+    output: impl Fn(Action)
+) {
+    let Some(space_id) = state.with_focused(|connection| connection.space.id()) else {
+        output(Action::TerminalPush(String::from("not connected to any space")));
+
+        return;
+    };
+
+    let name = name.to_string();
+
+    let room = state.database.spawn(move |database| {
+        Ok(PublicRoomRecord::find(database, space_id, name)?)
+    }).await;
+
+    match room {
+        Ok(Some(room)) => output(Action::OpenRoom(room)),
+        Ok(None) => output(Action::TerminalPush(String::from("room with such name doesn't exist"))),
+        Err(err) => output(Action::TerminalPush(format!("failed to find room: {err}")))
+    }
+}
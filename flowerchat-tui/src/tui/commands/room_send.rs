@@ -0,0 +1,43 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// flowerchat
+// Copyright (C) 2025  Nikita Podvirnyi <krypt0nn@vk.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use flowerchat_protocol::events::rooms::public_message::PublicRoomMessageEvent;
+
+use crate::tui::app::{AppState, Action};
+
+use super::send_event;
+
+pub async fn run(
+    state: AppState,
+    message: impl AsRef<str>,
+    output: impl Fn(Action)
+) {
+    let Some(room) = state.room.read().as_ref().map(|room| room.name.clone()) else {
+        output(Action::TerminalPush(String::from("No room is opened")));
+
+        return;
+    };
+
+    let Some(event) = PublicRoomMessageEvent::new(room, message) else {
+        output(Action::TerminalPush(String::from("Message is invalid")));
+
+        return;
+    };
+
+    send_event::run(state, event, output).await;
+}
@@ -0,0 +1,79 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// flowerchat
+// Copyright (C) 2025  Nikita Podvirnyi <krypt0nn@vk.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use libflowerpot::crypto::*;
+
+use crate::database::user::UserRecord;
+use crate::tui::app::{AppState, Action};
+use crate::tui::room_view::author_name;
+use crate::utils::safety_number;
+
+pub async fn run(
+    state: AppState,
+    user: impl ToString,
+    output: impl Fn(Action)
+) {
+    let focused = state.with_focused(|connection| {
+        (connection.space.id(), connection.identity.public_key())
+    });
+
+    let Some((space_id, identity)) = focused else {
+        output(Action::TerminalPush(String::from("not connected to any space")));
+
+        return;
+    };
+
+    let user = user.to_string();
+
+    let public_key = state.database.spawn(move |database| {
+        match UserRecord::find_by_nickname(database, space_id, &user)? {
+            Some(record) => Ok(Some(record.public_key()?)),
+            None => Ok(PublicKey::from_base64(user))
+        }
+    }).await;
+
+    let public_key = match public_key {
+        Ok(Some(public_key)) => public_key,
+
+        Ok(None) => {
+            output(Action::TerminalPush(String::from("user with such nickname or public key doesn't exist")));
+
+            return;
+        }
+
+        Err(err) => {
+            output(Action::TerminalPush(format!("failed to find user: {err}")));
+
+            return;
+        }
+    };
+
+    let safety_number = safety_number(identity.to_bytes(), public_key.to_bytes());
+
+    let mut lines = vec![
+        format!("safety number with {}:", author_name(&public_key, None))
+    ];
+
+    for line in safety_number.split(' ').collect::<Vec<_>>().chunks(4) {
+        lines.push(format!("  {}", line.join(" ")));
+    }
+
+    lines.push(String::from("compare it with the one shown to the other user to verify their public key"));
+
+    output(Action::TerminalPush(lines.join("\n")));
+}
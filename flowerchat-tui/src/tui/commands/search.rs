@@ -0,0 +1,261 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// flowerchat
+// Copyright (C) 2025  Nikita Podvirnyi <krypt0nn@vk.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use anyhow::Context;
+use time::{Date, Month, UtcDateTime, UtcOffset};
+
+use crate::database::public_room::PublicRoomRecord;
+use crate::database::public_message::{
+    PublicRoomMessageRecord, PublicRoomMessagesSearch, PublicRoomMessageSearchResult
+};
+use crate::tui::app::{AppState, Action};
+use crate::tui::room_view::author_name;
+use crate::utils::make_table;
+
+/// Maximal amount of returned search results.
+const RESULTS_LIMIT: usize = 20;
+
+/// Maximal length of the message content shown in the search results.
+const PREVIEW_LENGTH: usize = 48;
+
+/// Parse `YYYY-MM-DD` date in the timezone with provided offset.
+fn parse_date(date: &str, offset: UtcOffset) -> anyhow::Result<UtcDateTime> {
+    let mut parts = date.splitn(3, '-');
+
+    let (Some(year), Some(month), Some(day)) = (parts.next(), parts.next(), parts.next()) else {
+        anyhow::bail!("invalid date format: YYYY-MM-DD expected");
+    };
+
+    let year = year.parse::<i32>().context("invalid date year")?;
+    let month = month.parse::<u8>().context("invalid date month")?;
+    let day = day.parse::<u8>().context("invalid date day")?;
+
+    let date = Date::from_calendar_date(year, Month::try_from(month)?, day)
+        .context("invalid date")?;
+
+    Ok(date.midnight().assume_offset(offset).to_utc())
+}
+
+/// Parse search query with `in:room`, `from:user`, `before:date` and
+/// `after:date` filters. Dates are in the timezone with provided offset.
+fn parse_query(
+    query: impl IntoIterator<Item = String>,
+    offset: UtcOffset
+) -> anyhow::Result<PublicRoomMessagesSearch> {
+    let mut search = PublicRoomMessagesSearch::default();
+    let mut text = Vec::new();
+
+    for word in query {
+        if let Some(room) = word.strip_prefix("in:") {
+            search.room = Some(room.trim_start_matches('#').to_string());
+        }
+
+        else if let Some(user) = word.strip_prefix("from:") {
+            search.from = Some(user.to_string());
+        }
+
+        else if let Some(date) = word.strip_prefix("before:") {
+            search.before = Some(parse_date(date, offset)?);
+        }
+
+        else if let Some(date) = word.strip_prefix("after:") {
+            search.after = Some(parse_date(date, offset)?);
+        }
+
+        else {
+            text.push(word);
+        }
+    }
+
+    if !text.is_empty() {
+        search.text = Some(text.join(" "));
+    }
+
+    Ok(search)
+}
+
+fn make_row(
+    message: PublicRoomMessageSearchResult,
+    offset: UtcOffset
+) -> [String; 5] {
+    let author = author_name(&message.public_key, message.nickname);
+
+    let timestamp = message.timestamp.to_offset(offset);
+    let content = message.content;
+
+    let preview = if content.chars().count() > PREVIEW_LENGTH {
+        format!("{}…", content.chars().take(PREVIEW_LENGTH).collect::<String>())
+    } else {
+        content
+    };
+
+    [
+        message.id.to_string(),
+        format!("#{}", message.room_name),
+        author,
+        format!(
+            "{}-{:02}-{:02} {:02}:{:02}",
+            timestamp.year(),
+            timestamp.month() as u8,
+            timestamp.day(),
+            timestamp.hour(),
+            timestamp.minute()
+        ),
+        preview
+    ]
+}
+
+pub async fn run(
+    state: AppState,
+    query: impl IntoIterator<Item = String>,
+    output: impl Fn(Action)
+) {
+    let Some(space_id) = state.with_focused(|connection| connection.space.id()) else {
+        output(Action::TerminalPush(String::from("not connected to any space")));
+
+        return;
+    };
+
+    // Dates of the query and results are in UTC if the local timezone is
+    // unknown, so tell the user about it.
+    let offset = match state.local_offset {
+        Some(offset) => offset,
+        None => {
+            output(Action::TerminalPush(String::from(
+                "local timezone is unknown, dates are in UTC"
+            )));
+
+            UtcOffset::UTC
+        }
+    };
+
+    let search = match parse_query(query, offset) {
+        Ok(search) => search,
+        Err(err) => {
+            output(Action::TerminalPush(format!("invalid search query: {err:#}")));
+
+            return;
+        }
+    };
+
+    let messages = state.database.spawn(move |database| {
+        PublicRoomMessageRecord::search(database, space_id, &search, RESULTS_LIMIT)
+            .context("failed to search messages")
+    }).await;
+
+    let rows = match messages {
+        Ok(messages) => messages.into_iter()
+            .map(|message| make_row(message, offset))
+            .collect::<Vec<_>>(),
+
+        Err(err) => {
+            output(Action::TerminalPush(format!("{err:#}")));
+
+            return;
+        }
+    };
+
+    if rows.is_empty() {
+        output(Action::TerminalPush(String::from("nothing found")));
+
+        return;
+    }
+
+    output(Action::TerminalPush(make_table(
+        ["#", "Room", "Author", "Time", "Message"],
+        rows
+    )));
+
+    output(Action::TerminalPush(String::from(
+        "use `jump <#>` to open the message in its room"
+    )));
+}
+
+/// Open room of the message with provided ID positioned at this message.
+pub async fn jump(
+    state: AppState,
+    message_id: i64,
+    output: impl Fn(Action)
+) {
+    let Some(space_id) = state.with_focused(|connection| connection.space.id()) else {
+        output(Action::TerminalPush(String::from("not connected to any space")));
+
+        return;
+    };
+
+    let room = state.database.spawn(move |database| {
+        let room_id = match PublicRoomMessageRecord::open(database.clone(), message_id) {
+            Ok(message) => message.room_id()?,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+            Err(err) => return Err(err.into())
+        };
+
+        let room = PublicRoomRecord::open_raw(database, room_id);
+        let room_space_id = room.space_id()?;
+
+        Ok(Some((room, room_space_id)))
+    }).await;
+
+    match room {
+        Ok(Some((room, room_space_id))) if room_space_id == space_id => {
+            output(Action::OpenRoomAt { room, message_id });
+        }
+
+        Ok(Some(_)) => output(Action::TerminalPush(String::from("message belongs to another space"))),
+        Ok(None) => output(Action::TerminalPush(String::from("message with such id doesn't exist"))),
+        Err(err) => output(Action::TerminalPush(format!("failed to find message: {err}")))
+    }
+}
+
+#[test]
+fn test_parse_query() -> anyhow::Result<()> {
+    let words = |query: &str| {
+        query.split_whitespace()
+            .map(String::from)
+            .collect::<Vec<String>>()
+    };
+
+    let search = parse_query(
+        words("in:#general hello from:alice world after:2025-01-01 before:2025-01-02"),
+        UtcOffset::UTC
+    )?;
+
+    assert_eq!(search.text.as_deref(), Some("hello world"));
+    assert_eq!(search.room.as_deref(), Some("general"));
+    assert_eq!(search.from.as_deref(), Some("alice"));
+    assert_eq!(search.after, Some(UtcDateTime::from_unix_timestamp(1735689600)?));
+    assert_eq!(search.before, Some(UtcDateTime::from_unix_timestamp(1735776000)?));
+
+    // Dates are midnights in the provided timezone.
+    let search = parse_query(words("after:2025-01-01"), UtcOffset::from_hms(3, 0, 0)?)?;
+
+    assert_eq!(search.after, Some(UtcDateTime::from_unix_timestamp(1735689600 - 3 * 3600)?));
+
+    assert_eq!(parse_query(words("in:random"), UtcOffset::UTC)?, PublicRoomMessagesSearch {
+        room: Some(String::from("random")),
+        ..PublicRoomMessagesSearch::default()
+    });
+
+    assert_eq!(parse_query(Vec::new(), UtcOffset::UTC)?, PublicRoomMessagesSearch::default());
+
+    for query in ["before:2025-13-01", "before:2025-02-30", "after:2025-01", "after:yesterday", "before:"] {
+        assert!(parse_query(words(query), UtcOffset::UTC).is_err(), "{query}");
+    }
+
+    Ok(())
+}
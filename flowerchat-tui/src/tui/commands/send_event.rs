@@ -0,0 +1,159 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// flowerchat
+// Copyright (C) 2025  Nikita Podvirnyi <krypt0nn@vk.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use rand_chacha::rand_core::RngCore;
+use time::UtcDateTime;
+
+use libflowerpot::transaction::Transaction;
+
+use flowerchat_protocol::events::{Event, Events};
+
+use crate::outbox::{self, Update};
+use crate::tui::app::{AppState, Action};
+use crate::tui::room_view::RoomViewMessage;
+use crate::utils::get_rng;
+
+/// Sign transaction with provided event, store it in the outbox and announce
+/// it to the active shards of the connected space.
+pub async fn run(
+    state: AppState,
+    event: impl Into<Events>,
+    output: impl Fn(Action)
+) {
+    let connection = state.with_focused(|connection| {
+        let shards = connection.announce_shards();
+
+        (
+            connection.space.id(),
+            connection.identity.clone(),
+            connection.client.clone(),
+            shards
+        )
+    });
+
+    let Some((space_id, identity, client, shards)) = connection else {
+        output(Action::TerminalPush(String::from("Not connected")));
+
+        return;
+    };
+
+    output(Action::TerminalSetCurrentLine(String::from("Building transaction...")));
+
+    let event = event.into();
+
+    let mut data = Vec::new();
+
+    if let Err(err) = event.serialize(&mut data) {
+        output(Action::TerminalSetCurrentLine(String::new()));
+        output(Action::TerminalPush(format!("Failed to create event: {err}")));
+
+        return;
+    };
+
+    let transaction = Transaction::create(
+        &identity,
+        get_rng().next_u64(),
+        data
+    );
+
+    output(Action::TerminalSetCurrentLine(String::new()));
+
+    let transaction = match transaction {
+        Ok(transaction) => transaction,
+        Err(err) => {
+            output(Action::TerminalPush(format!("Failed to create transaction: {err}")));
+
+            return;
+        }
+    };
+
+    output(Action::TerminalPush(format!(
+        "Building transaction... {}",
+        transaction.hash().to_base64()
+    )));
+
+    // Show the message in the room right away, it will be replaced by its
+    // stored version once the transaction is included into the blockchain.
+    let echo = RoomViewMessage::local_echo(
+        transaction.hash(),
+        &identity.public_key(),
+        &event,
+        UtcDateTime::now()
+    );
+
+    let room_name = echo.map(|(room_name, message)| {
+        output(Action::LocalEcho {
+            space_id,
+            room_name: room_name.clone(),
+            message
+        });
+
+        room_name
+    });
+
+    let author = identity.public_key();
+
+    let record = state.database.spawn(move |database| {
+        Ok(outbox::enqueue(database, space_id, author, room_name, transaction)?)
+    }).await;
+
+    let mut record = match record {
+        Ok(record) => record,
+        Err(err) => {
+            output(Action::TerminalPush(format!("Failed to store transaction in the outbox: {err}")));
+
+            return;
+        }
+    };
+
+    output(Action::RefreshRoom);
+
+    output(Action::TerminalSetCurrentLine(format!(
+        "Announcing transaction to {} active shards...",
+        shards.len()
+    )));
+
+    let result = outbox::announce(&client, &shards, &mut record).await;
+
+    output(Action::TerminalSetCurrentLine(String::new()));
+
+    match result {
+        Ok(Update::Announced { error: None, .. }) => {
+            output(Action::TerminalPush(format!(
+                "Announcing transaction to {} active shards... Done",
+                shards.len()
+            )));
+
+            output(Action::RefreshRoom);
+        }
+
+        Ok(Update::Announced { error: Some(err), .. }) => {
+            output(Action::TerminalPush(format!(
+                "Announcing transaction to {} active shards... Error",
+                shards.len()
+            )));
+
+            output(Action::TerminalPush(format!("Failed to announce transaction: {err}")));
+            output(Action::TerminalPush(String::from("Transaction is queued and will be announced again later")));
+        }
+
+        Ok(Update::Failed { .. } | Update::Error(_)) => (),
+
+        Err(err) => output(Action::TerminalPush(format!("Failed to announce transaction: {err}")))
+    }
+}
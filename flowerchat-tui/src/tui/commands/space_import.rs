@@ -0,0 +1,55 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// flowerchat
+// Copyright (C) 2025  Nikita Podvirnyi <krypt0nn@vk.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use flowerchat_protocol::share_link::ShareLink;
+
+use crate::database::space::SpaceRecord;
+use crate::tui::app::{AppState, Action};
+
+pub async fn run(
+    state: AppState,
+    link: String,
+    output: impl Fn(Action)
+) {
+    let link = match ShareLink::parse(link) {
+        Ok(link) => link,
+        Err(err) => {
+            output(Action::TerminalPush(format!("invalid share link: {err}")));
+
+            return;
+        }
+    };
+
+    let result = state.database.spawn(move |database| {
+        let space = SpaceRecord::import(database, &link)?;
+
+        Ok((space.id(), space.title()?))
+    }).await;
+
+    match result {
+        Ok((id, title)) if title.is_empty() => {
+            output(Action::TerminalPush(format!("space imported: #{id}")));
+        }
+
+        Ok((id, title)) => {
+            output(Action::TerminalPush(format!("space imported: #{id} {title}")));
+        }
+
+        Err(err) => output(Action::TerminalPush(format!("failed to import space: {err}")))
+    }
+}
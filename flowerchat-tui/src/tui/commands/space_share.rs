@@ -0,0 +1,110 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// flowerchat
+// Copyright (C) 2025  Nikita Podvirnyi <krypt0nn@vk.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use tokio::sync::oneshot::channel as oneshot_channel;
+
+use libflowerpot::client::Client;
+
+use flowerchat_protocol::share_link::ShareLink;
+
+use crate::shards;
+use crate::tui::app::{AppState, Action};
+
+pub async fn run(
+    state: AppState,
+    space: impl ToString,
+    max_shards: usize,
+    public_only: bool,
+    output: impl Fn(Action)
+) {
+    let (send, recv) = oneshot_channel();
+
+    output(Action::RequestSpaceRecord(space.to_string(), send));
+
+    let space = match recv.await {
+        Ok(Ok(space)) => space,
+        Ok(Err(err)) => {
+            output(Action::TerminalPush(format!("failed to find space: {err}")));
+
+            return;
+        }
+
+        Err(_) => return
+    };
+
+    let space_id = space.id();
+
+    let result = state.database.spawn(move |_| {
+        Ok((space.load()?, space.shards()?))
+    }).await;
+
+    let (info, known_shards) = match result {
+        Ok(result) => result,
+        Err(err) => {
+            output(Action::TerminalPush(format!("failed to read space: {err}")));
+
+            return;
+        }
+    };
+
+    // Prefer scores and active shards of the connected space.
+    let connected = state.connections.read()
+        .iter()
+        .find(|connection| connection.space.id() == space_id)
+        .map(|connection| {
+            let active = connection.shards_pool.active()
+                .cloned()
+                .collect::<Vec<String>>();
+
+            (active, connection.shard_scores.clone())
+        });
+
+    let (shards, scores) = match connected {
+        Some((active, scores)) => (active.into_iter().chain(known_shards).collect(), scores),
+        None => {
+            output(Action::TerminalSetCurrentLine(format!(
+                "Probing {} shards...",
+                known_shards.len()
+            )));
+
+            let scores = shards::probe_all(
+                &Client::default(),
+                info.root_block,
+                known_shards.clone()
+            ).await;
+
+            output(Action::TerminalSetCurrentLine(String::new()));
+
+            (known_shards, scores)
+        }
+    };
+
+    let shards = shards::share_link_shards(
+        &scores,
+        shards,
+        max_shards,
+        public_only
+    );
+
+    let link = ShareLink::new(info.root_block, info.author, shards);
+
+    match link.to_uri() {
+        Ok(uri) => output(Action::TerminalPush(uri)),
+        Err(err) => output(Action::TerminalPush(format!("failed to make share link: {err}")))
+    }
+}
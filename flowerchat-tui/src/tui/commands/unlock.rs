@@ -0,0 +1,84 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// flowerchat
+// Copyright (C) 2025  Nikita Podvirnyi <krypt0nn@vk.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use zeroize::Zeroizing;
+
+use crate::identities;
+use crate::tui::app::{AppState, Action, SecretPrompt};
+
+/// Ask user to enter the passphrase. It's handled by the `run` function.
+pub fn prompt(state: AppState, output: impl Fn(Action)) {
+    let message = if identities::is_encrypted() {
+        "enter passphrase to unlock identities"
+    } else if identities::exists() {
+        "enter passphrase to encrypt identities"
+    } else {
+        "enter passphrase to encrypt new identities"
+    };
+
+    *state.new_passphrase.write() = None;
+
+    state.ask_secret(SecretPrompt::Passphrase, message, output);
+}
+
+pub async fn run(
+    state: AppState,
+    passphrase: String,
+    output: impl Fn(Action)
+) {
+    // New passphrase must be entered twice so a typo doesn't make the
+    // identities inaccessible.
+    if !identities::is_encrypted() {
+        let new_passphrase = state.new_passphrase.write().take();
+
+        match new_passphrase {
+            None => {
+                *state.new_passphrase.write() = Some(Zeroizing::new(passphrase));
+
+                state.ask_secret(SecretPrompt::Passphrase, "repeat passphrase", output);
+
+                return;
+            }
+
+            Some(new_passphrase) if *new_passphrase != passphrase => {
+                output(Action::TerminalPush(String::from("passphrases don't match")));
+
+                prompt(state, output);
+
+                return;
+            }
+
+            Some(_) => ()
+        }
+    }
+
+    output(Action::TerminalSetCurrentLine(String::from("unlocking identities...")));
+
+    let result = state.modify_identities(move |identities| {
+        identities.unlock(passphrase)?;
+
+        Ok(identities.identities().map(<[_]>::len).unwrap_or_default())
+    }).await;
+
+    output(Action::TerminalSetCurrentLine(String::new()));
+
+    match result {
+        Ok(len) => output(Action::TerminalPush(format!("unlocked {len} identities"))),
+        Err(err) => output(Action::TerminalPush(format!("failed to unlock identities: {err}")))
+    }
+}
@@ -17,6 +17,11 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use std::io::Stdout;
+use std::time::Duration;
+
+use time::UtcOffset;
+
+use zeroize::Zeroizing;
 
 use tokio::runtime::Handle;
 use tokio::task::JoinHandle;
@@ -30,9 +35,11 @@ use ratatui::widgets::*;
 use ratatui::text::*;
 use ratatui::style::*;
 
+use crate::consts::*;
 use crate::database::Database;
 
 pub mod terminal_widget;
+pub mod room_view;
 pub mod commands;
 pub mod app;
 
@@ -62,9 +69,11 @@ const FLOWERCHAT_LOGO: &str = r#"
 pub async fn run_app(
     runtime: Handle,
     database: Database,
+    identities_timeout: Option<Duration>,
+    local_offset: Option<UtcOffset>,
     terminal: &mut Terminal<CrosstermBackend<Stdout>>
 ) -> anyhow::Result<()> {
-    let state = app::AppState::new(database);
+    let state = app::AppState::new(database, identities_timeout, local_offset);
 
     let (actions_sender, mut updates_receiver) = app::run_actions_handler(
         runtime.clone(),
@@ -82,6 +91,12 @@ pub async fn run_app(
 
     drop(lock);
 
+    if crate::identities::exists() {
+        commands::unlock::prompt(state.clone(), |action| {
+            let _ = actions_sender.send(action);
+        });
+    }
+
     let mut running_command: Option<JoinHandle<()>> = None;
     let mut force_render = true;
 
@@ -116,13 +131,64 @@ pub async fn run_app(
         terminal.draw(|frame| {
             let block = Block::bordered();
 
-            let terminal_area = match &*state.connection.read() {
+            let connections = state.connections.read();
+
+            let terminal_area = match connections.focused_index() {
                 // Render connected chat.
-                Some(_connection) => {
+                Some(focused) => {
+                    let [spaces_area, chat_area] = Layout::vertical([
+                        Constraint::Length(1),
+                        Constraint::Fill(1)
+                    ]).areas(frame.area());
+
+                    // Render spaces switcher.
+                    let titles = connections.iter()
+                        .enumerate()
+                        .map(|(i, connection)| {
+                            if connection.unread > 0 {
+                                format!("F{} {} ({})", i + 1, connection.title, connection.unread)
+                            } else {
+                                format!("F{} {}", i + 1, connection.title)
+                            }
+                        });
+
+                    let spaces = Tabs::new(titles)
+                        .select(focused)
+                        .highlight_style(Style::new().fg(TUI_PRIMARY_COLOR).bold());
+
+                    frame.render_widget(spaces, spaces_area);
+
                     let [public_rooms_area, terminal_area] = Layout::horizontal([
                         Constraint::Percentage(20),
                         Constraint::Percentage(80)
-                    ]).areas(frame.area());
+                    ]).areas(chat_area);
+
+                    let terminal_area = match &*state.room.read() {
+                        Some(room) => {
+                            let [room_area, terminal_area] = Layout::vertical([
+                                Constraint::Percentage(70),
+                                Constraint::Percentage(30)
+                            ]).areas(terminal_area);
+
+                            let room_block = Block::bordered()
+                                .title_top(Line::styled(
+                                    format!("#{}", room.name),
+                                    Style::new().fg(TUI_PRIMARY_COLOR)
+                                ));
+
+                            let room_inner_area = room_block.inner(room_area);
+
+                            frame.render_widget(room_block, room_area);
+
+                            let messages = room.lines(room_inner_area.height as usize);
+
+                            frame.render_widget(List::new(messages), room_inner_area);
+
+                            terminal_area
+                        }
+
+                        None => terminal_area
+                    };
 
                     let terminal_inner_area = block.inner(terminal_area);
 
@@ -152,6 +218,8 @@ pub async fn run_app(
                 }
             };
 
+            drop(connections);
+
             // Update terminal properties and render it.
 
             let mut terminal_widget = state.terminal_widget.write();
@@ -185,6 +253,13 @@ pub async fn run_app(
                     Event::Key(key) => match key.code {
                         KeyCode::Esc => return Ok(()),
 
+                        // Switch focused space.
+                        KeyCode::F(n @ 1..=9) => {
+                            let _ = actions_sender.send(app::Action::Focus(n as usize - 1));
+
+                            break;
+                        }
+
                         KeyCode::Char(char) => {
                             let mut terminal_widget = state.terminal_widget.write();
 
@@ -235,6 +310,46 @@ pub async fn run_app(
                             }
                         }
 
+                        KeyCode::Enter if state.terminal_widget.read().secret_input => {
+                            let mut terminal_widget = state.terminal_widget.write();
+
+                            let TerminalWidgetCurrentLine::Input(secret) = terminal_widget.forbid_user_input() else {
+                                continue;
+                            };
+
+                            terminal_widget.prefix = None;
+                            terminal_widget.secret_input = false;
+
+                            let actions_sender = actions_sender.clone();
+
+                            let output = move |action| {
+                                let _ = actions_sender.send(action);
+                            };
+
+                            let prompt = state.secret_prompt.write()
+                                .take()
+                                .unwrap_or(app::SecretPrompt::Passphrase);
+
+                            let task = match prompt {
+                                app::SecretPrompt::Passphrase => runtime.spawn(commands::unlock::run(
+                                    state.clone(),
+                                    secret,
+                                    output
+                                )),
+
+                                prompt => runtime.spawn(commands::identity::add_secret(
+                                    state.clone(),
+                                    prompt,
+                                    Zeroizing::new(secret),
+                                    output
+                                ))
+                            };
+
+                            running_command = Some(task);
+
+                            break;
+                        }
+
                         KeyCode::Enter => {
                             let mut terminal_widget = state.terminal_widget.write();
                             let mut command = None;
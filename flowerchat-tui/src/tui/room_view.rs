@@ -0,0 +1,428 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// flowerchat
+// Copyright (C) 2025  Nikita Podvirnyi <krypt0nn@vk.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::Context;
+use time::{UtcDateTime, UtcOffset};
+
+use ratatui::text::*;
+use ratatui::style::*;
+
+use libflowerpot::crypto::*;
+
+use flowerchat_protocol::events::{Event, Events};
+
+use crate::consts::*;
+use crate::database::user::UserRecord;
+use crate::database::public_room::{
+    PublicRoomRecord, PublicRoomInfo, PublicRoomMessagesCursor
+};
+use crate::database::public_message::PublicRoomMessageRecord;
+use crate::database::outbox::{OutboxRecord, OutboxStatus};
+use crate::utils::{bytes_to_emoji, bytes_to_shortname};
+
+/// Time after which our pending message is flagged as undelivered if it's
+/// still not included into the blockchain.
+pub const LOCAL_ECHO_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RoomViewMarker {
+    /// Message of another user.
+    None,
+
+    /// Our message which is not included into the blockchain yet.
+    Pending,
+
+    /// Our message which is included into the blockchain.
+    Confirmed,
+
+    /// Our message which is still not included into the blockchain after
+    /// `LOCAL_ECHO_TIMEOUT`.
+    Undelivered,
+
+    /// Our message which was not included into the blockchain after all the
+    /// announcement attempts.
+    Failed
+}
+
+impl RoomViewMarker {
+    pub fn span(&self) -> Span<'static> {
+        match self {
+            Self::None        => Span::raw(""),
+            Self::Pending     => Span::styled(" …", Style::new().fg(TUI_DISABLED_COLOR)),
+            Self::Confirmed   => Span::styled(" ✓", Style::new().fg(TUI_PRIMARY_COLOR)),
+            Self::Undelivered => Span::styled(" ⚠ not delivered yet", Style::new().fg(Color::Yellow)),
+            Self::Failed      => Span::styled(" ✗ not delivered", Style::new().fg(Color::Red))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RoomViewMessage {
+    /// Hash of the transaction of the message.
+    pub transaction_hash: Hash,
+
+    /// Display name of the message's sender.
+    pub author: String,
+
+    /// Formatted time of when the message was approved by a validator.
+    pub time: Option<String>,
+
+    /// Content of the message.
+    pub content: String,
+
+    /// Delivery marker of the message.
+    pub marker: RoomViewMarker,
+
+    /// Timestamp of when we sent the message, if it's our pending message.
+    pub sent_at: Option<UtcDateTime>
+}
+
+impl RoomViewMessage {
+    /// Build local echo of our outgoing message from its signed transaction.
+    /// Return `None` if the transaction doesn't contain a room message.
+    pub fn local_echo(
+        transaction_hash: Hash,
+        public_key: &PublicKey,
+        event: &Events,
+        sent_at: UtcDateTime
+    ) -> Option<(String, Self)> {
+        let Events::PublicRoomMessage(event) = event else {
+            return None;
+        };
+
+        let message = Self {
+            transaction_hash,
+            author: author_name(public_key, None),
+            time: None,
+            content: event.content().to_string(),
+            marker: RoomViewMarker::Pending,
+            sent_at: Some(sent_at)
+        };
+
+        Some((event.room_name().to_string(), message))
+    }
+
+    /// Delivery marker of the message at the current time.
+    pub fn marker(&self) -> RoomViewMarker {
+        match (self.marker, self.sent_at) {
+            (RoomViewMarker::Pending, Some(sent_at))
+                if UtcDateTime::now() - sent_at > LOCAL_ECHO_TIMEOUT => RoomViewMarker::Undelivered,
+
+            (marker, _) => marker
+        }
+    }
+
+    pub fn line(&self) -> Line<'static> {
+        let time = self.time.as_deref()
+            .unwrap_or("--:--");
+
+        let marker = self.marker();
+
+        // Dim our messages which are not confirmed yet.
+        let style = match marker {
+            RoomViewMarker::None |
+            RoomViewMarker::Confirmed => Style::new(),
+
+            _ => Style::new().fg(TUI_DISABLED_COLOR)
+        };
+
+        Line::from(vec![
+            Span::styled(format!("[{time}] "), Style::new().fg(TUI_DISABLED_COLOR)),
+            Span::styled(format!("{}: ", self.author), style.bold()),
+            Span::styled(self.content.clone(), style),
+            marker.span()
+        ])
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RoomView {
+    pub room: PublicRoomRecord,
+    pub space_id: i64,
+    pub name: String,
+
+    /// ID of the message the view is positioned at. If `None`, the latest
+    /// messages are shown.
+    pub anchor: Option<i64>,
+
+    /// Index of the anchor message in the `messages` list.
+    pub anchor_index: Option<usize>,
+
+    /// Messages included into the blockchain.
+    pub messages: Vec<RoomViewMessage>,
+
+    /// Our messages which are not included into the blockchain yet.
+    pub pending: Vec<RoomViewMessage>,
+
+    /// Offset of the timezone messages time is shown in.
+    pub offset: UtcOffset
+}
+
+impl RoomView {
+    /// Maximal amount of the latest room messages to display.
+    pub const MESSAGES_LIMIT: usize = 100;
+
+    /// Load latest messages of the room and our outgoing messages sent to it.
+    /// Messages time is shown in the timezone with provided offset.
+    #[inline]
+    pub fn load(room: PublicRoomRecord, offset: UtcOffset) -> anyhow::Result<Self> {
+        Self::load_at(room, None, offset)
+    }
+
+    /// Load messages of the room around the message with provided ID, or the
+    /// latest ones if `anchor` is `None`.
+    pub fn load_at(
+        room: PublicRoomRecord,
+        anchor: Option<i64>,
+        offset: UtcOffset
+    ) -> anyhow::Result<Self> {
+        let database = room.database().clone();
+
+        let PublicRoomInfo { name, space_id, .. } = room.load()
+            .context("failed to load room")?;
+
+        let outbox = OutboxRecord::room_messages(&database, space_id, &name)
+            .context("failed to get outgoing messages")?;
+
+        let mut pending = Vec::with_capacity(outbox.len());
+
+        for outgoing in outbox {
+            let Ok(event) = Events::deserialize(&mut outgoing.transaction.data()) else {
+                continue;
+            };
+
+            let echo = RoomViewMessage::local_echo(
+                outgoing.transaction.hash(),
+                &outgoing.author,
+                &event,
+                outgoing.created_at
+            );
+
+            if let Some((_, mut message)) = echo {
+                if outgoing.status == OutboxStatus::Failed {
+                    message.marker = RoomViewMarker::Failed;
+                }
+
+                pending.push(message);
+            }
+        }
+
+        let records = match anchor {
+            Some(anchor) => {
+                let timestamp = PublicRoomMessageRecord::open_raw(database.clone(), anchor)
+                    .timestamp()
+                    .context("failed to get anchor message")?;
+
+                // Include the anchor message into the backward page.
+                let mut records = room.messages(
+                    PublicRoomMessagesCursor::BeforeTimestamp(timestamp, anchor + 1),
+                    Self::MESSAGES_LIMIT / 2
+                ).context("failed to get room messages")?;
+
+                records.extend(room.messages(
+                    PublicRoomMessagesCursor::AfterTimestamp(timestamp, anchor),
+                    Self::MESSAGES_LIMIT / 2
+                ).context("failed to get room messages")?);
+
+                records
+            }
+
+            None => room.messages(PublicRoomMessagesCursor::Latest, Self::MESSAGES_LIMIT)
+                .context("failed to get room messages")?
+        };
+
+        let anchor_index = anchor.and_then(|anchor| {
+            records.iter().position(|(id, _)| *id == anchor)
+        });
+
+        let outgoing = OutboxRecord::room_transactions(&database, space_id, &name)
+            .context("failed to get outgoing transactions")?;
+
+        let mut authors = HashMap::new();
+        let mut messages = Vec::with_capacity(records.len());
+
+        for (_, message) in records {
+            let author = match authors.get(&message.user_id) {
+                Some(author) => author,
+                None => {
+                    let user = UserRecord::open_raw(database.clone(), message.user_id)
+                        .load()
+                        .context("failed to load message sender")?;
+
+                    authors.entry(message.user_id)
+                        .or_insert(author_name(&user.public_key, user.nickname))
+                }
+            };
+
+            messages.push(RoomViewMessage {
+                transaction_hash: message.transaction_hash,
+                author: author.clone(),
+                time: Some(format_time(message.timestamp, offset)),
+                content: message.content,
+                marker: if outgoing.contains(&message.transaction_hash) {
+                    RoomViewMarker::Confirmed
+                } else {
+                    RoomViewMarker::None
+                },
+                sent_at: None
+            });
+        }
+
+        Ok(Self {
+            room,
+            space_id,
+            name,
+            anchor,
+            anchor_index,
+            messages,
+            pending,
+            offset
+        })
+    }
+
+    /// Reload messages of the current room.
+    pub fn reload(&mut self) -> anyhow::Result<()> {
+        let mut room = Self::load_at(self.room.clone(), self.anchor, self.offset)?;
+
+        // Keep local echoes which were not stored in the outbox yet.
+        for message in self.pending.drain(..) {
+            room.push_local_echo(message);
+        }
+
+        *self = room;
+
+        Ok(())
+    }
+
+    /// Show our outgoing message in the room before it's stored in the outbox
+    /// or included into the blockchain.
+    pub fn push_local_echo(&mut self, message: RoomViewMessage) {
+        let is_known = self.messages.iter()
+            .chain(self.pending.iter())
+            .any(|known| known.transaction_hash == message.transaction_hash);
+
+        if !is_known {
+            self.pending.push(message);
+        }
+    }
+
+    /// Remove local echo of the message with provided transaction hash.
+    /// Return `true` if there was such echo.
+    pub fn reconcile(&mut self, transaction_hash: &Hash) -> bool {
+        let len = self.pending.len();
+
+        self.pending.retain(|message| &message.transaction_hash != transaction_hash);
+
+        self.pending.len() != len
+    }
+
+    /// Get lines of the room messages which fit the given height.
+    pub fn lines(&self, height: usize) -> Vec<Line<'static>> {
+        // Keep the anchor message in the middle of the view.
+        if let Some(anchor_index) = self.anchor_index {
+            return self.messages.iter()
+                .enumerate()
+                .skip(anchor_index.saturating_sub(height / 2))
+                .take(height)
+                .map(|(i, message)| {
+                    if i == anchor_index {
+                        message.line().patch_style(Style::new().reversed())
+                    } else {
+                        message.line()
+                    }
+                })
+                .collect();
+        }
+
+        let len = self.messages.len() + self.pending.len();
+
+        self.messages.iter()
+            .chain(self.pending.iter())
+            .skip(len.saturating_sub(height))
+            .map(RoomViewMessage::line)
+            .collect()
+    }
+}
+
+/// Get display name of the user with provided public key and nickname.
+pub fn author_name(public_key: &PublicKey, nickname: Option<String>) -> String {
+    let public_key = public_key.to_bytes();
+
+    format!(
+        "{} {}",
+        bytes_to_emoji(public_key),
+        nickname.unwrap_or_else(|| bytes_to_shortname(public_key))
+    )
+}
+
+fn format_time(timestamp: UtcDateTime, offset: UtcOffset) -> String {
+    let timestamp = timestamp.to_offset(offset);
+
+    format!("{:02}:{:02}", timestamp.hour(), timestamp.minute())
+}
+
+#[test]
+fn test_local_echo() -> anyhow::Result<()> {
+    use crate::database::Database;
+
+    let message = |transaction_hash: u8, marker, sent_at| RoomViewMessage {
+        transaction_hash: Hash::from([transaction_hash; 32]),
+        author: String::from("alice"),
+        time: None,
+        content: String::from("hello"),
+        marker,
+        sent_at
+    };
+
+    let now = UtcDateTime::now();
+    let expired = now - LOCAL_ECHO_TIMEOUT - Duration::from_secs(1);
+
+    assert_eq!(message(1, RoomViewMarker::Pending, Some(now)).marker(), RoomViewMarker::Pending);
+    assert_eq!(message(1, RoomViewMarker::Pending, Some(expired)).marker(), RoomViewMarker::Undelivered);
+    assert_eq!(message(1, RoomViewMarker::Pending, None).marker(), RoomViewMarker::Pending);
+    assert_eq!(message(1, RoomViewMarker::Confirmed, Some(expired)).marker(), RoomViewMarker::Confirmed);
+    assert_eq!(message(1, RoomViewMarker::Failed, Some(expired)).marker(), RoomViewMarker::Failed);
+
+    let mut view = RoomView {
+        room: PublicRoomRecord::open_raw(Database::open_in_memory()?, 1),
+        space_id: 1,
+        name: String::from("general"),
+        anchor: None,
+        anchor_index: None,
+        messages: vec![message(1, RoomViewMarker::Confirmed, None)],
+        pending: Vec::new(),
+        offset: UtcOffset::UTC
+    };
+
+    // Echoes of the already known messages are ignored.
+    view.push_local_echo(message(1, RoomViewMarker::Pending, Some(now)));
+    view.push_local_echo(message(2, RoomViewMarker::Pending, Some(now)));
+    view.push_local_echo(message(2, RoomViewMarker::Pending, Some(now)));
+
+    assert_eq!(view.pending.len(), 1);
+
+    assert!(view.reconcile(&Hash::from([2; 32])));
+    assert!(!view.reconcile(&Hash::from([2; 32])));
+
+    assert!(view.pending.is_empty());
+
+    Ok(())
+}
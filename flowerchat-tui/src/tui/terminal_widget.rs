@@ -39,6 +39,10 @@ pub struct TerminalWidget {
     pub history: Vec<String>,
     pub ongoing: TerminalWidgetCurrentLine,
     pub prefix: Option<String>,
+
+    /// Hide user's input, e.g. when a passphrase is entered.
+    pub secret_input: bool,
+
     pub offset: Option<usize>,
     pub width: u16,
     pub height: u16
@@ -105,6 +109,10 @@ impl TerminalWidget {
             .collect::<Vec<String>>();
 
         match &self.ongoing {
+            TerminalWidgetCurrentLine::Input(text) if self.secret_input => {
+                lines.push(self.prefix("*".repeat(text.chars().count())));
+            }
+
             TerminalWidgetCurrentLine::Input(text) => {
                 lines.push(self.prefix(text));
             }
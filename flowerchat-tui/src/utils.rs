@@ -184,3 +184,70 @@ pub fn bytes_to_shortname(bytes: impl AsRef<[u8]>) -> String {
 
     name
 }
+
+/// Get fingerprint of the bytes slice (e.g. a public key) as 30 decimal
+/// digits. Unlike the emoji and short name, fingerprint is long enough to
+/// not to be forged.
+pub fn bytes_to_fingerprint(bytes: impl AsRef<[u8]>) -> String {
+    let hash = blake3::derive_key("flowerchat public key fingerprint v1", bytes.as_ref());
+
+    hash.chunks_exact(5)
+        .take(6)
+        .map(|chunk| {
+            let number = chunk.iter()
+                .fold(0u64, |number, byte| (number << 8) | *byte as u64);
+
+            format!("{:05}", number % 100000)
+        })
+        .collect()
+}
+
+/// Get safety number of two public keys. It's the same for both sides, so
+/// two users can verify each other's keys by comparing it in person.
+///
+/// Safety number consists of 60 decimal digits split into groups of 5.
+pub fn safety_number(
+    public_key_a: impl AsRef<[u8]>,
+    public_key_b: impl AsRef<[u8]>
+) -> String {
+    let mut fingerprints = [
+        bytes_to_fingerprint(public_key_a),
+        bytes_to_fingerprint(public_key_b)
+    ];
+
+    fingerprints.sort();
+
+    fingerprints.concat()
+        .as_bytes()
+        .chunks(5)
+        .map(|group| String::from_utf8_lossy(group).to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Render QR code of the data using unicode half-block characters, so it can
+/// be scanned from the terminal screen. Colors are inverted because most of
+/// terminals have dark background.
+pub fn qr_code(data: impl AsRef<[u8]>) -> Result<String, qrcode::types::QrError> {
+    use qrcode::render::unicode::Dense1x2;
+
+    let code = qrcode::QrCode::new(data)?
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .quiet_zone(true)
+        .build();
+
+    Ok(code)
+}
+
+#[test]
+fn test_safety_number() {
+    let number = safety_number(b"alice", b"bob");
+
+    assert_eq!(number, safety_number(b"bob", b"alice"));
+    assert_ne!(number, safety_number(b"alice", b"eve"));
+
+    assert_eq!(number.split(' ').count(), 12);
+    assert!(number.split(' ').all(|group| group.len() == 5 && group.chars().all(|c| c.is_ascii_digit())));
+}